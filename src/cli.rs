@@ -0,0 +1,277 @@
+use std::fs;
+use std::path::PathBuf;
+use lettre::transport::smtp::authentication::Credentials;
+use crate::dedup::DuplicateAction;
+use crate::{dedup, domains, excel, history, html, log, mail, AuthState, MailData, SendStatus, State, SAVED_FILE};
+
+const USAGE: &str = "用法:
+  kt-mail                      打开图形界面
+  kt-mail send [选项]          不打开界面，直接导入、生成并发送邮件
+  kt-mail help                 显示本帮助
+
+send 的选项:
+  --file <路径>                Excel 文件（必填）
+  --sheet <名称>               工作表，默认 Sheet1
+  --map <映射>                 指定邮箱、序号、姓名所在的列，如 email=工作邮箱,seq=工号,name=姓名，默认依次取前三列
+  --subject <主题>             邮件主题（必填），可使用 {{列名}}
+  --intro <引言>               邮件引言
+  --remark <备注>              邮件提示信息
+  --template <路径>            HTML 模板，默认使用内置模板
+  --profile <路径>             账户配置，默认 ./auth.dll（登录界面勾选保存密码后生成）
+  --interval <毫秒>            两封邮件之间的最小间隔，默认 0
+  --duplicates <first|all|merge>
+                               重复邮箱只保留第一条、全部保留或合并为一封，默认 first
+  --allow-external             允许发送到账户允许域名以外的地址
+  --result <路径>              结果文件（JSON），默认 ./result-批次号.json
+
+退出码: 0 全部发送成功，1 有邮件发送失败，2 参数或配置有误";
+
+#[derive(Debug)]
+struct Options {
+    file: PathBuf,
+    sheet: String,
+    mapping: excel::Mapping,
+    subject: String,
+    intro: String,
+    remark: String,
+    template: String,
+    profile: String,
+    interval: u64,
+    duplicates: DuplicateAction,
+    allow_external: bool,
+    result: Option<String>,
+}
+
+/// 第一个参数是已知的子命令时才按命令行运行；其它参数（关联打开的文件、macOS 的 `-psn_…` 等）照常打开界面
+pub fn is_command(arg: &str) -> bool {
+    matches!(arg, "send" | "help" | "-h" | "--help")
+}
+
+/// 命令行入口，返回进程的退出码
+pub fn run(args: &[String]) -> i32 {
+    match args[0].as_str() {
+        "send" => match parse(&args[1..]).and_then(|options| send(&options)) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("错误: {}", e);
+                2
+            }
+        },
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            0
+        }
+        command => {
+            eprintln!("未知的命令: {}\n\n{}", command, USAGE);
+            2
+        }
+    }
+}
+
+fn parse(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        file: PathBuf::new(),
+        sheet: "Sheet1".to_owned(),
+        mapping: excel::Mapping::default(),
+        subject: String::new(),
+        intro: String::new(),
+        remark: String::new(),
+        template: String::new(),
+        profile: SAVED_FILE.to_owned(),
+        interval: 0,
+        duplicates: DuplicateAction::KeepFirst,
+        allow_external: false,
+        result: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--allow-external" {
+            options.allow_external = true;
+            continue;
+        }
+        let value = args.next().ok_or_else(|| format!("{} 缺少参数值", arg))?.clone();
+        match arg.as_str() {
+            "--file" => options.file = PathBuf::from(value),
+            "--sheet" => options.sheet = value,
+            "--map" => options.mapping = parse_mapping(&value)?,
+            "--subject" => options.subject = value,
+            "--intro" => options.intro = value,
+            "--remark" => options.remark = value,
+            "--template" => options.template = value,
+            "--profile" => options.profile = value,
+            "--interval" => options.interval = value.parse().map_err(|_| format!("--interval 必须是整数: {}", value))?,
+            "--duplicates" => {
+                options.duplicates = match value.as_str() {
+                    "first" => DuplicateAction::KeepFirst,
+                    "all" => DuplicateAction::KeepAll,
+                    "merge" => DuplicateAction::Merge,
+                    _ => return Err(format!("--duplicates 只能是 first、all 或 merge: {}", value)),
+                }
+            }
+            "--result" => options.result = Some(value),
+            _ => return Err(format!("未知的选项: {}", arg)),
+        }
+    }
+    if options.file.as_os_str().is_empty() {
+        return Err("缺少 --file".to_owned());
+    }
+    if options.subject.trim().is_empty() {
+        return Err("缺少 --subject".to_owned());
+    }
+    Ok(options)
+}
+
+fn parse_mapping(value: &str) -> Result<excel::Mapping, String> {
+    let mut mapping = excel::Mapping::default();
+    for pair in value.split([',', '，']).filter(|pair| !pair.trim().is_empty()) {
+        let (field, column) = pair.split_once('=').ok_or_else(|| format!("列映射格式不正确: {}", pair))?;
+        let column = Some(column.trim().to_owned());
+        match field.trim() {
+            "email" => mapping.email = column,
+            "seq" => mapping.seq = column,
+            "name" => mapping.name = column,
+            field => return Err(format!("未知的映射字段: {}，只能是 email、seq 或 name", field)),
+        }
+    }
+    Ok(mapping)
+}
+
+fn send(options: &Options) -> Result<i32, String> {
+    let data = fs::read_to_string(&options.profile).map_err(|e| format!("读取账户配置 {} 失败: {}", options.profile, e))?;
+    let mut auth: AuthState = serde_json::from_str(&data).map_err(|e| format!("账户配置 {} 格式有误: {}", options.profile, e))?;
+    if auth.username.is_empty() || auth.password.is_empty() {
+        return Err(format!("账户配置 {} 中没有账户名或密码", options.profile));
+    }
+    auth.allowed_domains = domains::resolve(&auth.username, &auth.allowed_domains)?;
+    // 模板读取失败时界面会改用默认模板，命令行下直接报错
+    html::load_template(&options.template)?;
+
+    let mut state = State::new(auth, MailData {
+        title: options.subject.trim().to_owned(),
+        intro: options.intro.trim().to_owned(),
+        remark: options.remark.trim().to_owned(),
+        template: options.template.clone(),
+        interval: options.interval,
+        ..MailData::default()
+    });
+    // 禁止发送名单读不出来时不能当作空名单发送
+    if let Some(e) = state.suppression_error.take() {
+        return Err(e);
+    }
+    let (mut list, headers) = excel::parse_excel(options.file.clone(), &options.sheet, &options.mapping)?;
+    let duplicates = dedup::mark(&mut list);
+    if duplicates > 0 {
+        println!("发现 {} 个重复的邮箱地址", duplicates);
+        log::warn("导入的数据中有重复的邮箱地址", &[("source", &options.file.to_string_lossy()), ("count", &duplicates.to_string())]);
+        match options.duplicates {
+            DuplicateAction::KeepFirst => dedup::keep_first(&mut list),
+            DuplicateAction::KeepAll => {}
+            DuplicateAction::Merge => dedup::merge(&mut list),
+        }
+    }
+    state.source = options.file.to_string_lossy().to_string();
+    state.source_hash = history::file_hash(&options.file);
+    state.list = list;
+    state.headers = headers;
+    state.apply_suppression();
+
+    let missing = state.missing_columns();
+    if !missing.is_empty() {
+        return Err(format!("模板引用了不存在的列: {}", missing.join("、")));
+    }
+    let domains = state.auth.domains();
+    let external: Vec<&str> = state.list.iter()
+        .filter(|task| task.status && !mail::domain_allowed(&task.email, &domains))
+        .map(|task| task.email.as_str())
+        .collect();
+    if !external.is_empty() {
+        if options.allow_external {
+            println!("以下 {} 个收件人不在允许的域名内，按 --allow-external 照常发送: {}", external.len(), external.join(", "));
+        } else {
+            println!("以下 {} 个收件人不在允许的域名内，将被跳过: {}", external.len(), external.join(", "));
+        }
+    }
+    state.allow_external = options.allow_external;
+
+    state.begin_campaign();
+    let creds = Credentials::new(state.auth.username.clone(), state.auth.password.clone());
+    let total = state.list.iter().filter(|task| task.status).count();
+    log::info("命令行开始发送", &[("campaign", &state.campaign), ("sender", &state.sender()), ("title", &state.title), ("count", &total.to_string())]);
+    println!("批次 {}，共 {} 行，将发送 {} 封邮件", state.campaign, state.list.len(), total);
+
+    let mut results = vec![];
+    let mut current = 0;
+    for task in &state.list {
+        if !task.status {
+            results.push(task.clone());
+            continue;
+        }
+        current += 1;
+        let result = state.send_task(task, &creds);
+        match result.send_status.error() {
+            "" => println!("[{}/{}] {} {}", current, total, result.email, result.send_status.label()),
+            reason => println!("[{}/{}] {} {}: {}", current, total, result.email, result.send_status.label(), reason),
+        }
+        results.push(result);
+    }
+
+    let record = state.campaign_record(&results);
+    if let Err(e) = history::append(record.clone()) {
+        log::error("保存发送记录失败", &[("campaign", &state.campaign), ("error", &e)]);
+        eprintln!("保存发送记录失败: {}", e);
+    }
+    let path = options.result.clone().unwrap_or_else(|| format!("./result-{}.json", state.campaign));
+    let data = serde_json::to_string_pretty(&record).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("写入结果文件 {} 失败: {}", path, e))?;
+
+    let count = |f: fn(&SendStatus) -> bool| results.iter().filter(|task| f(&task.send_status)).count();
+    let sent = count(|status| *status == SendStatus::Sent);
+    let failed = count(|status| matches!(status, SendStatus::Failed(_)));
+    let skipped = count(|status| matches!(status, SendStatus::Skipped(_)));
+    println!("发送完毕: 成功 {}，失败 {}，跳过 {}，结果已写入 {}", sent, failed, skipped, path);
+    log::info("命令行发送结束", &[("campaign", &state.campaign), ("sent", &sent.to_string()), ("failed", &failed.to_string())]);
+
+    Ok(if failed > 0 { 1 } else { 0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn allow_external_takes_no_value() {
+        let options = parse(&args(&["--allow-external", "--file", "工资条.xlsx", "--subject", "工资条"])).unwrap();
+        assert!(options.allow_external);
+        assert_eq!(options.file, PathBuf::from("工资条.xlsx"));
+        assert_eq!(options.subject, "工资条");
+        assert_eq!(options.profile, SAVED_FILE);
+        assert_eq!(options.duplicates, DuplicateAction::KeepFirst);
+    }
+
+    #[test]
+    fn flag_without_value_is_an_error() {
+        assert_eq!(parse(&args(&["--subject", "工资条", "--file"])).unwrap_err(), "--file 缺少参数值");
+        assert_eq!(parse(&args(&["--file", "a.xlsx", "--subject"])).unwrap_err(), "--subject 缺少参数值");
+        assert_eq!(parse(&args(&["--file", "a.xlsx"])).unwrap_err(), "缺少 --subject");
+        assert!(parse(&args(&["--file", "a.xlsx", "--subject", "s", "--interval", "abc"])).is_err());
+        assert!(parse(&args(&["--file", "a.xlsx", "--subject", "s", "--bogus", "1"])).unwrap_err().contains("未知的选项"));
+    }
+
+    #[test]
+    fn mapping_accepts_both_commas() {
+        let mapping = parse_mapping("email=工作邮箱，name = 姓名 ,").unwrap();
+        assert_eq!(mapping.email.as_deref(), Some("工作邮箱"));
+        assert_eq!(mapping.seq, None);
+        assert_eq!(mapping.name.as_deref(), Some("姓名"));
+    }
+
+    #[test]
+    fn mapping_rejects_unknown_fields_and_bad_pairs() {
+        assert!(parse_mapping("phone=电话").unwrap_err().contains("未知的映射字段"));
+        assert!(parse_mapping("email").unwrap_err().contains("格式不正确"));
+    }
+}
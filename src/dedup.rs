@@ -0,0 +1,105 @@
+use std::collections::{HashMap, HashSet};
+use crate::mail::is_valid_email;
+use crate::Tasks;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateAction {
+    KeepFirst,
+    KeepAll,
+    Merge,
+}
+
+// 比较时忽略大小写和首尾空白；空的或格式不对的邮箱不参与去重，留给发送前的检查去提示
+fn key(task: &Tasks) -> Option<String> {
+    let email = task.email.trim().to_lowercase();
+    is_valid_email(&email).then_some(email)
+}
+
+fn counts(list: &[Tasks]) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for key in list.iter().filter_map(key) {
+        *counts.entry(key).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// 标记邮箱重复的行，返回重复的邮箱个数
+pub fn mark(list: &mut [Tasks]) -> usize {
+    let counts = counts(list);
+    for task in list.iter_mut() {
+        task.duplicate = key(task).is_some_and(|key| counts[&key] > 1);
+    }
+    counts.values().filter(|count| **count > 1).count()
+}
+
+/// 同一邮箱只保留第一次出现的行
+pub fn keep_first(list: &mut Vec<Tasks>) {
+    let mut seen = HashSet::new();
+    list.retain(|task| key(task).is_none_or(|key| seen.insert(key)));
+}
+
+/// 同一邮箱的行并入第一次出现的行，发送时合成一封邮件
+pub fn merge(list: &mut Vec<Tasks>) {
+    let mut merged: Vec<Tasks> = vec![];
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for mut task in list.drain(..) {
+        let key = key(&task);
+        match key.as_ref().and_then(|key| positions.get(key)) {
+            Some(position) => {
+                let rows = std::mem::take(&mut task.merged);
+                let first = &mut merged[*position];
+                first.merged.push(task);
+                first.merged.extend(rows);
+            }
+            None => {
+                if let Some(key) = key {
+                    positions.insert(key, merged.len());
+                }
+                merged.push(task);
+            }
+        }
+    }
+    *list = merged;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks(emails: &[&str]) -> Vec<Tasks> {
+        emails.iter().enumerate().map(|(id, email)| Tasks { id, email: email.to_string(), ..Tasks::default() }).collect()
+    }
+
+    fn ids(list: &[Tasks]) -> Vec<usize> {
+        list.iter().map(|task| task.id).collect()
+    }
+
+    #[test]
+    fn mark_ignores_case_and_whitespace() {
+        let mut list = tasks(&["a@example.com", " A@Example.com ", "b@example.com"]);
+        assert_eq!(mark(&mut list), 1);
+        assert_eq!(list.iter().map(|task| task.duplicate).collect::<Vec<_>>(), [true, true, false]);
+    }
+
+    #[test]
+    fn empty_and_invalid_emails_are_not_duplicates() {
+        let mut list = tasks(&["", " ", "not-an-email", "not-an-email", "a@example.com"]);
+        assert_eq!(mark(&mut list), 0);
+        assert!(list.iter().all(|task| !task.duplicate));
+
+        keep_first(&mut list);
+        assert_eq!(ids(&list), [0, 1, 2, 3, 4]);
+
+        merge(&mut list);
+        assert_eq!(ids(&list), [0, 1, 2, 3, 4]);
+        assert!(list.iter().all(|task| task.merged.is_empty()));
+    }
+
+    #[test]
+    fn merge_folds_rows_into_the_first() {
+        let mut list = tasks(&["a@example.com", "", "A@example.com", ""]);
+        merge(&mut list);
+        assert_eq!(ids(&list), [0, 1, 3]);
+        assert_eq!(ids(&list[0].merged), [2]);
+    }
+}
@@ -0,0 +1,40 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use crate::DOMAINS_FILE;
+
+// 账户名不区分大小写
+fn key(username: &str) -> String {
+    username.trim().to_lowercase()
+}
+
+fn read() -> Result<BTreeMap<String, String>, String> {
+    match fs::read_to_string(DOMAINS_FILE) {
+        Ok(data) => serde_json::from_str(&data).map_err(|e| format!("收件域名设置 {} 已损坏: {}", DOMAINS_FILE, e)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(format!("读取收件域名设置 {} 失败: {}", DOMAINS_FILE, e)),
+    }
+}
+
+/// 该账户保存过的允许域名，从未保存过时返回 `None`；文件损坏时返回错误，不能当作不限制
+pub fn load(username: &str) -> Result<Option<String>, String> {
+    Ok(read()?.remove(&key(username)))
+}
+
+/// 保存该账户的允许域名，其它账户的设置保持不变；文件损坏时不覆盖
+pub fn save(username: &str, domains: &str) -> Result<(), String> {
+    let mut all = read()?;
+    all.insert(key(username), domains.trim().to_owned());
+    let data = serde_json::to_string(&all).map_err(|e| e.to_string())?;
+    fs::write(DOMAINS_FILE, data).map_err(|e| format!("写入收件域名设置失败: {}", e))
+}
+
+/// 登录时决定使用的允许域名：填写了就保存为该账户的设置，没填则沿用保存过的设置。
+/// 旧版本保存在 auth.dll 中的域名也会由此迁移过来
+pub fn resolve(username: &str, entered: &str) -> Result<String, String> {
+    if entered.trim().is_empty() {
+        return Ok(load(username)?.unwrap_or_default());
+    }
+    save(username, entered)?;
+    Ok(entered.trim().to_owned())
+}
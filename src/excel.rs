@@ -1,57 +1,131 @@
 use std::path::PathBuf;
-use calamine::{open_workbook, DataType, Reader, Xls, Xlsx};
+use calamine::{open_workbook_auto, DataType, Reader};
 use crate::{Header, Tasks};
 
-pub fn parse_excel(path: PathBuf) -> (Vec<Tasks>, Vec<Header>) {
-    let ext = path.extension().unwrap();
+/// 邮箱、序号、姓名分别取自哪一列，未指定时依次取前三列
+#[derive(Debug, Clone, Default)]
+pub struct Mapping {
+    pub email: Option<String>,
+    pub seq: Option<String>,
+    pub name: Option<String>,
+}
+
+impl Mapping {
+    // 指定的三列排在最前面，其余列保持原来的顺序
+    fn order(&self, names: &[String]) -> Result<Vec<usize>, String> {
+        let mut order: Vec<usize> = (0..names.len()).collect();
+        for (target, column) in [&self.email, &self.seq, &self.name].into_iter().enumerate() {
+            let Some(column) = column else {
+                continue;
+            };
+            if [&self.email, &self.seq, &self.name][..target].contains(&&Some(column.clone())) {
+                return Err(format!("列 {} 不能同时用作多个字段", column));
+            }
+            let source = names.iter()
+                .position(|name| name == column)
+                .ok_or_else(|| format!("找不到列: {}", column))?;
+            let current = order.iter().position(|index| *index == source).unwrap();
+            let index = order.remove(current);
+            order.insert(target.min(order.len()), index);
+        }
+        Ok(order)
+    }
+}
+
+pub fn parse_excel(path: PathBuf, sheet: &str, mapping: &Mapping) -> Result<(Vec<Tasks>, Vec<Header>), String> {
     let mut headers = vec![];
     let mut tasks_list = vec![];
-    if ext == "xlsx" {
-        let mut workbook: Xlsx<_> = open_workbook(path).expect("Failed to open Excel file");
-    } else if ext == "xls" {
-        let mut workbook: Xls<_> = open_workbook(path).expect("Failed to open Excel file");
-        if let Ok(range) = workbook.worksheet_range("Sheet1") {
-            let mut first_line = true;
-            for row in range.rows() {
-                if first_line {
-                    headers = row.iter()
-                        .map(|cell| {
-                            let mut name = cell.to_string();
-                            name = name.replace("\n", "");
-                            let mut width = 100.0;
-                            if name == "邮箱地址" {
-                                width = 250.0
-                            }
-                            Header { name, width, check: true }
-                        }).collect();
-                    first_line = false;
-                    headers.insert(0, Header { name: "全选".to_owned(), width: 50.0, check: true });
-                    continue;
-                }
-                let mut tasks = Tasks::default();
-                let mut info = vec! {};
-                for (i, v) in row.iter().enumerate() {
-                    if i == 0 {
-                        tasks.email = v.to_string();
-                    } else if i == 1 {
-                        if v.is_int() {
-                            tasks.seq = v.get_int().unwrap_or(0);
-                        } else if v.is_float() {
-                            tasks.seq = v.get_float().unwrap_or(0f64) as i64;
-                        } else {
-                            tasks.seq = 0;
-                        }
-                    } else if i == 2 {
-                        tasks.name = v.to_string();
-                    } else {
-                        info.push(format!("{}", v));
-                    }
+    let mut workbook = open_workbook_auto(&path).map_err(|e| format!("打开 {} 失败: {}", path.display(), e))?;
+    let range = workbook.worksheet_range(sheet).map_err(|e| format!("读取工作表 {} 失败: {}", sheet, e))?;
+    let mut order = vec![];
+    for row in range.rows() {
+        if order.is_empty() {
+            let names: Vec<String> = row.iter().map(|cell| cell.to_string().replace("\n", "")).collect();
+            order = mapping.order(&names)?;
+            headers = build_headers(order.iter().map(|index| names[*index].clone()).collect());
+            continue;
+        }
+        let mut tasks = Tasks {
+            id: tasks_list.len(),
+            ..Tasks::default()
+        };
+        let mut info = vec! {};
+        for (i, v) in order.iter().map(|index| row.get(*index)).enumerate() {
+            let Some(v) = v else {
+                continue;
+            };
+            if i == 0 {
+                tasks.email = v.to_string();
+            } else if i == 1 {
+                if v.is_int() {
+                    tasks.seq = v.get_int().unwrap_or(0);
+                } else if v.is_float() {
+                    tasks.seq = v.get_float().unwrap_or(0f64) as i64;
+                } else {
+                    tasks.seq = 0;
                 }
-                tasks.info = info;
-                tasks.status = true;
-                tasks_list.push(tasks);
+            } else if i == 2 {
+                tasks.name = v.to_string();
+            } else {
+                info.push(format!("{}", v));
             }
         }
+        tasks.info = info;
+        tasks.status = true;
+        tasks_list.push(tasks);
+    }
+    Ok((tasks_list, headers))
+}
+
+/// 根据表头名称生成表格列，前面加上固定的"全选"和"发送状态"两列
+pub fn build_headers(names: Vec<String>) -> Vec<Header> {
+    let mut headers: Vec<Header> = names.into_iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let mut width = 100.0;
+            if name == "邮箱地址" {
+                width = 250.0
+            }
+            Header { name, index, width, check: true, ..Header::default() }
+        }).collect();
+    headers.insert(0, Header { name: "全选".to_owned(), width: 50.0, check: true, ..Header::default() });
+    headers.insert(1, Header { name: "发送状态".to_owned(), width: 160.0, ..Header::default() });
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> Vec<String> {
+        ["部门", "工号", "工作邮箱", "姓名"].map(String::from).to_vec()
+    }
+
+    fn mapping(email: Option<&str>, seq: Option<&str>, name: Option<&str>) -> Mapping {
+        Mapping { email: email.map(String::from), seq: seq.map(String::from), name: name.map(String::from) }
+    }
+
+    #[test]
+    fn unmapped_columns_keep_their_order() {
+        assert_eq!(Mapping::default().order(&names()).unwrap(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn mapped_columns_move_to_the_front() {
+        let order = mapping(Some("工作邮箱"), Some("工号"), Some("姓名")).order(&names()).unwrap();
+        assert_eq!(order, [2, 1, 3, 0]);
+        // 只指定了邮箱时，序号和姓名依次取其余的列
+        assert_eq!(mapping(Some("工作邮箱"), None, None).order(&names()).unwrap(), [2, 0, 1, 3]);
+    }
+
+    #[test]
+    fn column_mapped_twice_is_an_error() {
+        let error = mapping(Some("工作邮箱"), None, Some("工作邮箱")).order(&names()).unwrap_err();
+        assert_eq!(error, "列 工作邮箱 不能同时用作多个字段");
+    }
+
+    #[test]
+    fn unknown_column_is_an_error() {
+        assert_eq!(mapping(None, Some("手机"), None).order(&names()).unwrap_err(), "找不到列: 手机");
     }
-    (tasks_list, headers)
-}
\ No newline at end of file
+}
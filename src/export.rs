@@ -0,0 +1,33 @@
+use std::fs;
+use std::path::Path;
+use crate::{Header, Tasks, FIXED_COLUMNS};
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// 将导入的数据连同发送状态、错误信息、尝试次数和发送时间写入 csv 文件
+pub fn write_csv(path: &Path, headers: &[Header], tasks: &[Tasks]) -> Result<(), String> {
+    let mut columns: Vec<String> = headers.iter().skip(FIXED_COLUMNS).map(|header| header.name.clone()).collect();
+    columns.extend(["发送状态", "错误信息", "尝试次数", "发送时间"].map(String::from));
+
+    // 带 BOM，Excel 直接打开时中文不会乱码
+    let mut csv = String::from("\u{feff}");
+    csv.push_str(&columns.iter().map(|column| csv_field(column)).collect::<Vec<_>>().join(","));
+    csv.push_str("\r\n");
+    // 合并的行各占一行，沿用所在邮件的发送结果
+    for (task, row) in tasks.iter().flat_map(|task| std::iter::once(task).chain(&task.merged).map(move |row| (task, row))) {
+        let mut fields: Vec<String> = headers.iter().skip(FIXED_COLUMNS).map(|header| row.value(header.index)).collect();
+        fields.push(task.send_status.label().to_owned());
+        fields.push(task.send_status.error().to_owned());
+        fields.push(task.attempts.to_string());
+        fields.push(task.sent_at.clone());
+        csv.push_str(&fields.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","));
+        csv.push_str("\r\n");
+    }
+    fs::write(path, csv).map_err(|e| format!("导出到 {} 失败: {}", path.display(), e))
+}
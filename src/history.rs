@@ -0,0 +1,241 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use iced::widget::{button, column, container, horizontal_space, row, scrollable, text, text_input};
+use iced::{alignment, theme, Color, Element, Length};
+use serde::{Deserialize, Serialize};
+use crate::{HistoryState, Message, SendStatus, HISTORY_FILE};
+
+/// 一次发送（批量、单行重发）的记录
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Campaign {
+    pub id: String,
+    pub title: String,
+    pub intro: String,
+    pub remark: String,
+    pub template: String,
+    pub source: String,
+    pub source_hash: String,
+    pub sender: String,
+    pub started_at: String,
+    pub ended_at: String,
+    /// 导入时的表头，按 Excel 中的列顺序
+    pub headers: Vec<String>,
+    pub recipients: Vec<Recipient>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "StoredRecipient")]
+pub struct Recipient {
+    pub email: String,
+    pub name: String,
+    pub status: SendStatus,
+    pub message_id: String,
+    pub sent_at: String,
+    /// 该行各列的值，顺序与 [`Campaign::headers`] 一致，用于重新打开这批数据
+    pub values: Vec<String>,
+}
+
+/// 读取时兼容旧版本的记录：状态保存为界面上的文字，原因单独保存在 `error` 中
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct StoredRecipient {
+    email: String,
+    name: String,
+    status: StoredStatus,
+    error: String,
+    message_id: String,
+    sent_at: String,
+    values: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredStatus {
+    Status(SendStatus),
+    Label(String),
+}
+
+impl Default for StoredStatus {
+    fn default() -> Self {
+        StoredStatus::Status(SendStatus::default())
+    }
+}
+
+impl From<StoredRecipient> for Recipient {
+    fn from(stored: StoredRecipient) -> Self {
+        let status = match stored.status {
+            StoredStatus::Status(status) => status,
+            StoredStatus::Label(label) => match label.as_str() {
+                "发送中" => SendStatus::Sending,
+                "发送成功" => SendStatus::Sent,
+                "发送失败" => SendStatus::Failed(stored.error),
+                "已跳过" => SendStatus::Skipped(stored.error),
+                _ => SendStatus::Pending,
+            },
+        };
+        Recipient {
+            email: stored.email,
+            name: stored.name,
+            status,
+            message_id: stored.message_id,
+            sent_at: stored.sent_at,
+            values: stored.values,
+        }
+    }
+}
+
+impl Campaign {
+    pub fn count(&self, f: fn(&SendStatus) -> bool) -> usize {
+        self.recipients.iter().filter(|recipient| f(&recipient.status)).count()
+    }
+
+    pub fn contains(&self, query: &str) -> bool {
+        self.recipients.iter().any(|recipient| recipient.matches(query))
+    }
+}
+
+impl Recipient {
+    /// 按邮箱或姓名查找，不区分大小写
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        query.is_empty() || self.email.to_lowercase().contains(&query) || self.name.to_lowercase().contains(&query)
+    }
+}
+
+/// 文件不存在时返回空列表；文件损坏时返回错误，不能当作没有记录，否则下次保存会把它覆盖掉
+pub fn load() -> Result<Vec<Campaign>, String> {
+    match fs::read_to_string(HISTORY_FILE) {
+        Ok(data) => serde_json::from_str(&data).map_err(|e| format!("发送记录 {} 已损坏: {}", HISTORY_FILE, e)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(format!("读取发送记录 {} 失败: {}", HISTORY_FILE, e)),
+    }
+}
+
+pub fn append(campaign: Campaign) -> Result<(), String> {
+    let mut campaigns = load()?;
+    campaigns.push(campaign);
+    let data = serde_json::to_string(&campaigns).map_err(|e| e.to_string())?;
+    // 先写临时文件再改名，写到一半中断时原来的记录还在
+    let temp = format!("{}.tmp", HISTORY_FILE);
+    fs::write(&temp, data)
+        .and_then(|_| fs::rename(&temp, HISTORY_FILE))
+        .map_err(|e| format!("写入发送记录失败: {}", e))
+}
+
+/// 计算导入文件的 FNV-1a 摘要，用来判断两次发送是否来自同一份文件
+pub fn file_hash(path: &Path) -> String {
+    let Ok(bytes) = fs::read(path) else {
+        return String::new();
+    };
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+pub fn view(state: &HistoryState) -> Element<'_, Message> {
+    let search = text_input("按收件人邮箱或姓名搜索", &state.search)
+        .on_input(Message::HistorySearch)
+        .padding(5)
+        .width(400);
+    let back = button("返回").padding([5, 10])
+        .on_press(Message::CloseHistory)
+        .style(theme::Button::Secondary);
+    let tools = row![search, horizontal_space(), back].spacing(20).align_items(alignment::Alignment::Center);
+
+    let mut campaigns = column![].spacing(5);
+    for (index, campaign) in state.campaigns.iter().enumerate() {
+        if !campaign.contains(&state.search) {
+            continue;
+        }
+        let style = if state.selected == Some(index) { theme::Button::Primary } else { theme::Button::Secondary };
+        let label = column![
+            text(&campaign.title),
+            text(format!("{}  成功 {} / 失败 {} / 共 {}", campaign.started_at,
+                         campaign.count(|status| *status == SendStatus::Sent), campaign.count(|status| matches!(status, SendStatus::Failed(_))), campaign.recipients.len())).size(14),
+        ].spacing(2);
+        campaigns = campaigns.push(button(label)
+            .width(Length::Fill)
+            .padding([5, 10])
+            .on_press(Message::SelectCampaign(index))
+            .style(style));
+    }
+    let campaigns = container(scrollable(campaigns)).width(420).height(Length::Fill);
+
+    let detail: Element<_> = match state.selected.and_then(|index| state.campaigns.get(index).map(|campaign| (index, campaign))) {
+        Some((index, campaign)) => detail(state, index, campaign),
+        None if state.campaigns.is_empty() => text("暂无发送记录").style(Color::from([0.7, 0.7, 0.7])).into(),
+        None => text("请在左侧选择一次发送").style(Color::from([0.7, 0.7, 0.7])).into(),
+    };
+
+    container(column![tools, row![campaigns, detail].spacing(20)].spacing(10))
+        .padding(20)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
+}
+
+fn detail<'a>(state: &'a HistoryState, index: usize, campaign: &'a Campaign) -> Element<'a, Message> {
+    let failed = campaign.count(|status| matches!(status, SendStatus::Failed(_)));
+    let mut reopen = button(text(format!("重新打开（勾选 {} 条失败记录）", failed))).padding([5, 10])
+        .style(theme::Button::Primary);
+    if !campaign.headers.is_empty() {
+        reopen = reopen.on_press(Message::ReopenCampaign(index));
+    }
+    let info = column![
+        text(format!("主题: {}", campaign.title)).size(18),
+        text(format!("发件人: {}", campaign.sender)),
+        text(format!("时间: {} ~ {}", campaign.started_at, campaign.ended_at)),
+        text(format!("数据来源: {}", campaign.source)),
+        text(format!("模板: {}", if campaign.template.is_empty() { "默认模板" } else { &campaign.template })),
+        reopen,
+    ].spacing(6);
+
+    let mut recipients = column![row![
+        text("邮箱地址").width(250),
+        text("姓名").width(100),
+        text("发送状态").width(80),
+        text("发送时间").width(160),
+        text("错误信息").width(Length::Fill),
+    ].spacing(10)].spacing(6);
+    for recipient in campaign.recipients.iter().filter(|recipient| recipient.matches(&state.search)) {
+        recipients = recipients.push(row![
+            text(&recipient.email).width(250),
+            text(&recipient.name).width(100),
+            text(recipient.status.label()).width(80).style(recipient.status.color()),
+            text(&recipient.sent_at).width(160),
+            text(recipient.status.error()).width(Length::Fill),
+        ].spacing(10));
+    }
+
+    column![info, scrollable(recipients).height(Length::Fill)]
+        .spacing(20)
+        .width(Length::Fill)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_status_labels_from_old_records() {
+        let recipient: Recipient = serde_json::from_str(r#"{"email":"a@example.com","status":"发送失败","error":"超时"}"#).unwrap();
+        assert_eq!(recipient.status, SendStatus::Failed("超时".to_owned()));
+        let recipient: Recipient = serde_json::from_str(r#"{"status":"发送成功","error":""}"#).unwrap();
+        assert_eq!(recipient.status, SendStatus::Sent);
+    }
+
+    #[test]
+    fn status_round_trips() {
+        for status in [SendStatus::Sent, SendStatus::Skipped("禁止发送".to_owned())] {
+            let recipient = Recipient { status: status.clone(), ..Recipient::default() };
+            let recipient: Recipient = serde_json::from_str(&serde_json::to_string(&recipient).unwrap()).unwrap();
+            assert_eq!(recipient.status, status);
+        }
+    }
+}
@@ -1,50 +1,179 @@
-use crate::{Header, Tasks};
-
-pub fn generate_html(tasks: &Tasks, headers: &[Header], remark: &str) -> String {
-    let div_header = "<div>";
-    let div_foot = "</div>";
-    let table_header = r#"<table border="0" cellspacing="1" cellpadding="0" width="1167" bgcolor=" #000000" height="14">"#;
-    let table_foot = "</table>";
-    let tr_header = r#"<tr bgcolor=" #ffffff" style="white-space: nowrap;">"#;
-    let tr_foot = "</tr>";
-    let mut html = format!("{}{}{}", div_header, table_header, tr_header);
-    for (index, header) in headers.iter().enumerate() {
-        if index == 0 {
-            continue;
+use std::fs;
+use chrono::Local;
+use crate::{template, Header, Tasks, FIXED_COLUMNS};
+
+pub const DEFAULT_TEMPLATE: &str = include_str!("../templates/default.html");
+
+const BUILTINS: [&str; 6] = ["column", "cell", "intro", "remark", "sender", "date"];
+
+pub fn load_template(path: &str) -> Result<String, String> {
+    if path.is_empty() {
+        return Ok(DEFAULT_TEMPLATE.to_owned());
+    }
+    fs::read_to_string(path).map_err(|e| format!("读取模板 {} 失败: {}", path, e))
+}
+
+/// 模板中引用的表格列名，不含内置变量和循环标记
+pub fn template_columns(layout: &str) -> Vec<String> {
+    template::placeholders(layout)
+        .into_iter()
+        .filter(|name| !name.starts_with('#') && !name.starts_with('/') && !BUILTINS.contains(&name.as_str()))
+        .collect()
+}
+
+/// 转义 HTML 特殊字符，避免表格数据破坏版式或注入标签
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// 勾选了"HTML"的列按原样输出，其余列一律转义
+fn cell_html(header: &Header, value: String) -> String {
+    if header.raw {
+        value
+    } else {
+        escape(&value)
+    }
+}
+
+/// 引言和提示信息是纯文本：占位符替换为转义后的单元格，其余文字转义
+struct Text<'a> {
+    task: &'a Tasks,
+    headers: &'a [Header],
+}
+
+impl template::Context for Text<'_> {
+    fn value(&self, name: &str) -> Option<String> {
+        template::column(self.headers, name).map(|header| cell_html(header, self.task.value(header.index)))
+    }
+
+    fn text(&self, literal: &str) -> String {
+        escape(literal)
+    }
+}
+
+/// 展开模板时所在的位置：`rows` 循环中的当前行、`columns`/`cells` 循环中的当前列
+#[derive(Clone, Copy)]
+struct Scope<'a> {
+    row: &'a Tasks,
+    header: Option<&'a Header>,
+    headers: &'a [Header],
+    intro: &'a str,
+    remark: &'a str,
+    sender: &'a str,
+    date: &'a str,
+}
+
+impl Scope<'_> {
+    fn each_column(&self, inner: &str) -> String {
+        self.headers.iter()
+            .skip(FIXED_COLUMNS)
+            .filter(|header| header.check)
+            .map(|header| template::expand(inner, &Scope { header: Some(header), ..*self }))
+            .collect()
+    }
+}
+
+impl template::Context for Scope<'_> {
+    // 内置变量优先于同名的表格列
+    fn value(&self, name: &str) -> Option<String> {
+        match (name, self.header) {
+            ("intro", _) => Some(self.intro.to_owned()),
+            ("remark", _) => Some(self.remark.to_owned()),
+            ("sender", _) => Some(escape(self.sender)),
+            ("date", _) => Some(self.date.to_owned()),
+            ("column", Some(header)) => Some(escape(&header.name)),
+            ("cell", Some(header)) => Some(cell_html(header, self.row.value(header.index))),
+            _ => template::column(self.headers, name).map(|header| cell_html(header, self.row.value(header.index))),
         }
-        html.push_str(&format!(r#"<td bgcolor=" #dbeef3" height="14" style="padding: 5px;">
-                <div align="left"><span
-                        style="font-family: 宋体, serif, EmojiFont; color: rgb(0, 0, 0); font-size: 11px; font-weight: bold;">{}</span>
-                </div>
-            </td>"#, header.name));
-    }
-
-    html.push_str(tr_foot);
-    html.push_str(tr_header);
-    html.push_str(&format!(r#"<td bgcolor=" #ffffff" height="17" style="padding: 5px;">
-                <div align="center"><span
-                        style="font-family: 宋体, serif, EmojiFont; color: rgb(0, 0, 0); font-size: 15px;">{}</span>
-                </div>
-            </td>"#, tasks.email));
-    html.push_str(&format!(r#"<td bgcolor=" #ffffff" height="17" style="padding: 5px;">
-                <div align="center"><span
-                        style="font-family: 宋体, serif, EmojiFont; color: rgb(0, 0, 0); font-size: 15px;">{}</span>
-                </div>
-            </td>"#, tasks.seq));
-    html.push_str(&format!(r#"<td bgcolor=" #ffffff" height="17" style="padding: 5px;">
-                <div align="center"><span
-                        style="font-family: 宋体, serif, EmojiFont; color: rgb(0, 0, 0); font-size: 15px;">{}</span>
-                </div>
-            </td>"#, tasks.name));
-
-    for task in tasks.info.iter() {
-        html.push_str(&format!(r#"<td bgcolor=" #ffffff" height="17" style="padding: 5px;">
-                <div align="center"><span
-                        style="font-family: 宋体, serif, EmojiFont; color: rgb(0, 0, 0); font-size: 15px;">{}</span>
-                </div>
-            </td>"#, task));
-    }
-    format!("{}{}{}
-    <p>&nbsp;</p>
-    *附：{}{}", html, tr_foot, table_foot, remark, div_foot)
-}
\ No newline at end of file
+    }
+
+    fn block(&self, name: &str, inner: &str) -> Option<String> {
+        match name {
+            "intro" | "remark" => {
+                let value = if name == "intro" { self.intro } else { self.remark };
+                Some(if value.is_empty() { String::new() } else { template::expand(inner, self) })
+            }
+            "columns" | "cells" => Some(self.each_column(inner)),
+            // 合并过的重复邮箱在同一封邮件里列出所有行
+            "rows" => Some(std::iter::once(self.row).chain(&self.row.merged)
+                .map(|row| template::expand(inner, &Scope { row, ..*self }))
+                .collect()),
+            _ => None,
+        }
+    }
+}
+
+pub fn generate_html(tasks: &Tasks, headers: &[Header], layout: &str, intro: &str, remark: &str, sender: &str) -> String {
+    let text = Text { task: tasks, headers };
+    let intro = template::expand(intro, &text);
+    let remark = template::expand(remark, &text);
+    let date = Local::now().format("%Y-%m-%d").to_string();
+    template::expand(layout, &Scope {
+        row: tasks,
+        header: None,
+        headers,
+        intro: &intro,
+        remark: &remark,
+        sender,
+        date: &date,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::excel::build_headers;
+
+    fn headers() -> Vec<Header> {
+        build_headers(["邮箱地址", "序号", "姓名", "remark"].map(String::from).to_vec())
+    }
+
+    fn task(name: &str, remark: &str) -> Tasks {
+        Tasks {
+            email: "zhang@example.com".to_owned(),
+            seq: 1,
+            name: name.to_owned(),
+            info: vec![remark.to_owned()],
+            ..Tasks::default()
+        }
+    }
+
+    #[test]
+    fn cell_values_are_not_expanded() {
+        let task = task("{{remark}}{{#rows}}x{{/rows}}", "{{date}}");
+        let html = generate_html(&task, &headers(), "{{姓名}}|{{#rows}}{{#cells}}[{{cell}}]{{/cells}}{{/rows}}", "", "备注", "");
+        assert_eq!(html, "{{remark}}{{#rows}}x{{/rows}}|[zhang@example.com][1][{{remark}}{{#rows}}x{{/rows}}][{{date}}]");
+    }
+
+    #[test]
+    fn builtins_take_precedence_over_columns() {
+        let html = generate_html(&task("张三", "列的值"), &headers(), "{{remark}}/{{sender}}", "", "提示", "发件人");
+        assert_eq!(html, "提示/发件人");
+    }
+
+    #[test]
+    fn intro_escapes_text_but_resolves_placeholders() {
+        let headers = build_headers(["邮箱地址", "序号", "姓名", "R&D"].map(String::from).to_vec());
+        let html = generate_html(&task("张三", "<b>"), &headers, "{{intro}}", "{{姓名}} & {{R&D}} {{未知<列>}}", "", "");
+        assert_eq!(html, "张三 &amp; &lt;b&gt; {{未知&lt;列&gt;}}");
+    }
+
+    #[test]
+    fn rows_include_merged_tasks() {
+        let mut first = task("张三", "");
+        first.merged.push(task("李四", ""));
+        let html = generate_html(&first, &headers(), "{{#intro}}不显示{{/intro}}{{#rows}}<{{姓名}}>{{/rows}}", "", "", "");
+        assert_eq!(html, "<张三><李四>");
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::fs;
+use serde::{Deserialize, Serialize};
+use crate::{Header, FIXED_COLUMNS, LAYOUT_FILE};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ColumnLayout {
+    name: String,
+    width: f32,
+}
+
+// 以导入时的表头顺序作为签名，同一份报表再次导入时沿用上次的列宽和列顺序
+fn signature(headers: &[Header]) -> String {
+    let mut names: Vec<(usize, &str)> = headers.iter()
+        .skip(FIXED_COLUMNS)
+        .map(|header| (header.index, header.name.as_str()))
+        .collect();
+    names.sort();
+    names.into_iter().map(|(_, name)| name).collect::<Vec<_>>().join("\u{1f}")
+}
+
+fn read_layouts() -> HashMap<String, Vec<ColumnLayout>> {
+    match fs::read_to_string(LAYOUT_FILE) {
+        Ok(data) => serde_json::from_str(&data).unwrap_or_default(),
+        Err(_) => HashMap::new(),
+    }
+}
+
+pub fn apply(headers: &mut Vec<Header>) {
+    let layouts = read_layouts();
+    let Some(columns) = layouts.get(&signature(headers)) else {
+        return;
+    };
+    let mut ordered: Vec<Header> = headers.drain(..FIXED_COLUMNS).collect();
+    for column in columns {
+        if let Some(position) = headers.iter().position(|header| header.name == column.name) {
+            let mut header = headers.remove(position);
+            header.width = column.width;
+            ordered.push(header);
+        }
+    }
+    ordered.append(headers);
+    *headers = ordered;
+}
+
+pub fn save(headers: &[Header]) {
+    let mut layouts = read_layouts();
+    layouts.insert(signature(headers), headers.iter()
+        .skip(FIXED_COLUMNS)
+        .map(|header| ColumnLayout { name: header.name.clone(), width: header.width })
+        .collect());
+    fs::write(LAYOUT_FILE, serde_json::to_string(&layouts).unwrap()).ok();
+}
@@ -1,19 +1,224 @@
+use std::collections::VecDeque;
+use std::env;
 use std::fs;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use chrono::Local;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
 
-const LOG_DIR: &'static str = "logs/";
+const LOG_DIR: &str = "logs/";
+// 界面中最多保留的本次运行日志条数
+const SESSION_LIMIT: usize = 2000;
 
-pub fn info(msg: &str) {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [Level::Debug, Level::Info, Level::Warn, Level::Error];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+}
+
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// 本次运行中记录的一条日志，供界面上的日志面板显示
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub time: String,
+    pub level: Level,
+    pub msg: String,
+    pub fields: Vec<(String, String)>,
+}
+
+impl Entry {
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub dir: String,
+    pub level: Level,
+    /// 单个日志文件的最大字节数，超过后写入 `日期.1.log`、`日期.2.log`……
+    pub max_size: u64,
+    /// 日志保留天数，0 表示不清理
+    pub retention_days: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            dir: LOG_DIR.to_owned(),
+            level: Level::Info,
+            max_size: 10 * 1024 * 1024,
+            retention_days: 30,
+        }
+    }
+}
+
+struct Logger {
+    config: Option<Config>,
+    cleaned: Option<NaiveDate>,
+    // 最近一次成功写入的目录，可能是退回后的临时目录
+    written: Option<PathBuf>,
+    session: VecDeque<Entry>,
+}
+
+static LOGGER: Mutex<Logger> = Mutex::new(Logger { config: None, cleaned: None, written: None, session: VecDeque::new() });
+
+/// 从配置文件读取日志设置，文件不存在或格式有误时使用默认值
+pub fn init(path: &str) {
+    let config = fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default();
+    configure(config);
+}
+
+pub fn configure(config: Config) {
+    let mut logger = LOGGER.lock().unwrap_or_else(|e| e.into_inner());
+    logger.config = Some(config);
+    logger.cleaned = None;
+}
+
+pub fn debug(msg: &str, fields: &[(&str, &str)]) {
+    log(Level::Debug, msg, fields);
+}
+
+pub fn info(msg: &str, fields: &[(&str, &str)]) {
+    log(Level::Info, msg, fields);
+}
+
+pub fn warn(msg: &str, fields: &[(&str, &str)]) {
+    log(Level::Warn, msg, fields);
+}
+
+pub fn error(msg: &str, fields: &[(&str, &str)]) {
+    log(Level::Error, msg, fields);
+}
+
+/// 本次运行记录的所有日志（不受配置的级别限制），按时间先后排列
+pub fn session() -> Vec<Entry> {
+    let logger = LOGGER.lock().unwrap_or_else(|e| e.into_inner());
+    logger.session.iter().cloned().collect()
+}
+
+/// 日志文件所在的目录
+pub fn dir() -> PathBuf {
+    let mut logger = LOGGER.lock().unwrap_or_else(|e| e.into_inner());
+    match &logger.written {
+        Some(dir) => dir.clone(),
+        None => PathBuf::from(&logger.config.get_or_insert_with(Config::default).dir),
+    }
+}
+
+/// 以 key=value 的形式追加一行日志
+pub fn log(level: Level, msg: &str, fields: &[(&str, &str)]) {
+    let mut logger = LOGGER.lock().unwrap_or_else(|e| e.into_inner());
+    let config = logger.config.get_or_insert_with(Config::default).clone();
     let now = Local::now();
-    let file_name = format!("{}{}.log", LOG_DIR, now.format("%Y-%m-%d"));
-    let mut file = match OpenOptions::new().append(true).open(&file_name) {
-        Ok(file) => file,
-        Err(_) => {
-            fs::create_dir_all(LOG_DIR).unwrap();
-            File::create(&file_name).unwrap()
+
+    if logger.session.len() >= SESSION_LIMIT {
+        logger.session.pop_front();
+    }
+    logger.session.push_back(Entry {
+        time: now.format("%H:%M:%S%.3f").to_string(),
+        level,
+        msg: msg.to_owned(),
+        fields: fields.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+    });
+    if level < config.level {
+        return;
+    }
+
+    let mut line = format!("time={} level={} msg={}", quote(&now.format("%Y-%m-%d %H:%M:%S%.3f").to_string()), level.as_str(), quote(msg));
+    for (key, value) in fields {
+        line.push_str(&format!(" {}={}", key, quote(value)));
+    }
+    line.push('\n');
+
+    let today = now.date_naive();
+    if config.retention_days > 0 && logger.cleaned != Some(today) {
+        logger.cleaned = Some(today);
+        clean(Path::new(&config.dir), config.retention_days);
+    }
+
+    // 日志目录不可写时退回到系统临时目录，再不行就输出到标准错误
+    let fallback = env::temp_dir().join("kt-mail-logs");
+    for dir in [PathBuf::from(&config.dir), fallback] {
+        if let Some(mut file) = open(&dir, &now.format("%Y-%m-%d").to_string(), config.max_size) {
+            if file.write_all(line.as_bytes()).is_ok() {
+                logger.written = Some(dir);
+                return;
+            }
+        }
+    }
+    eprint!("{}", line);
+}
+
+fn open(dir: &Path, date: &str, max_size: u64) -> Option<File> {
+    fs::create_dir_all(dir).ok()?;
+    let mut index = 0;
+    loop {
+        let name = if index == 0 {
+            format!("{}.log", date)
+        } else {
+            format!("{}.{}.log", date, index)
+        };
+        let path = dir.join(name);
+        let full = max_size > 0 && fs::metadata(&path).is_ok_and(|meta| meta.len() >= max_size);
+        if !full {
+            return OpenOptions::new().create(true).append(true).open(path).ok();
         }
+        index += 1;
+    }
+}
+
+fn clean(dir: &Path, retention_days: u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let Some(deadline) = SystemTime::now().checked_sub(Duration::from_secs(retention_days * 24 * 60 * 60)) else {
+        return;
     };
-    file.write_all(msg.as_bytes()).ok();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "log")
+            && entry.metadata().and_then(|meta| meta.modified()).is_ok_and(|modified| modified < deadline) {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.contains([' ', '"', '=', '\n', '\r', '\t']) {
+        return value.to_owned();
+    }
+    let escaped = value.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
 }
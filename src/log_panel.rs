@@ -0,0 +1,51 @@
+use iced::widget::{button, column, container, horizontal_space, pick_list, row, scrollable, text};
+use iced::{alignment, theme, Color, Element, Length};
+use crate::log::{Entry, Level};
+use crate::{Message, State};
+
+pub fn view(state: &State) -> Element<'_, Message> {
+    let level = pick_list(&Level::ALL[..], Some(state.log_level), Message::LogLevel);
+    let open_dir = button("打开日志目录").padding([5, 10])
+        .on_press(Message::OpenLogDir)
+        .style(theme::Button::Secondary);
+    let close = button("收起").padding([5, 10])
+        .on_press(Message::ToggleLog)
+        .style(theme::Button::Secondary);
+    let tools = row![text("本次运行日志"), level, horizontal_space(), open_dir, close]
+        .spacing(10)
+        .align_items(alignment::Alignment::Center);
+
+    let mut lines = column![].spacing(4);
+    for entry in state.log_entries.iter().filter(|entry| entry.level >= state.log_level) {
+        lines = lines.push(line(entry));
+    }
+
+    container(column![tools, scrollable(lines).id(state.log_scroll.clone()).width(Length::Fill).height(Length::Fill)].spacing(10))
+        .width(Length::Fill)
+        .height(220)
+        .padding(10)
+        .style(theme::Container::Box)
+        .into()
+}
+
+// 错误信息单独成行完整显示，不截断
+fn line(entry: &Entry) -> Element<'_, Message> {
+    let color = match entry.level {
+        Level::Debug => Color::from([0.5, 0.5, 0.5]),
+        Level::Info => Color::BLACK,
+        Level::Warn => Color::from([0.9, 0.5, 0.0]),
+        Level::Error => Color::from([0.8, 0.2, 0.2]),
+    };
+    let fields = entry.fields.iter()
+        .filter(|(key, _)| key != "error")
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let summary = text(format!("{} {:5} {} {}", entry.time, entry.level.as_str(), entry.msg, fields))
+        .size(14)
+        .style(color);
+    match entry.field("error") {
+        Some(error) => column![summary, text(error).size(14).style(color)].spacing(2).into(),
+        None => summary.into(),
+    }
+}
@@ -1,34 +1,110 @@
+use std::error::Error;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 use lettre::message::header::ContentType;
 use lettre::{Message, SmtpTransport, Transport};
 use lettre::transport::smtp::authentication::Credentials;
+use crate::log;
 
-pub fn send_mail(subject: &str, body: &str, sender: &str, receiver: &str, creds: Credentials) -> Result<(), String> {
+#[derive(Debug, Clone)]
+pub struct Receipt {
+    pub message_id: String,
+    pub response: String,
+}
+
+pub fn send_mail(subject: &str, body: &str, sender: &str, receiver: &str, creds: Credentials) -> Result<Receipt, String> {
+    let from = format!("<{}>", sender).parse().map_err(|e| format!("发件地址 {} 无效: {}", sender, e))?;
+    let to = format!("<{}>", receiver).parse().map_err(|e| format!("收件地址 {} 无效: {}", receiver, e))?;
     let email = Message::builder()
-        .from(format!("<{}>", sender).parse().unwrap())
-        .to(format!("<{}>", receiver).parse().unwrap())
+        .from(from)
+        .to(to)
         .subject(subject)
+        .message_id(None)
         .header(ContentType::TEXT_HTML)
         .body(String::from(body))
-        .unwrap();
+        .map_err(|e| format!("Error building email: {}", e))?;
+    let message_id = email.headers().get_raw("Message-ID").unwrap_or_default().to_owned();
 
     let mailer = SmtpTransport::builder_dangerous("smtp.wondersgroup.com")
         .credentials(creds)
         .build();
 
     match mailer.send(&email) {
-        Ok(_) => Ok(()),
+        Ok(response) => Ok(Receipt {
+            message_id,
+            response: format!("{} {}", response.code(), response.message().collect::<Vec<_>>().join(" ")),
+        }),
         Err(e) => {
-            Err(format!("Error sending email: {}", e))
+            Err(format!("Error sending email: {}", full_error(&e)))
         }
     }
 }
 
+// lettre 的错误信息只带第一层原因，这里把完整的原因链都带上，方便排查 SMTP 问题
+fn full_error(e: &dyn Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        let cause_text = cause.to_string();
+        if !message.contains(&cause_text) {
+            message.push_str(&format!(": {}", cause_text));
+        }
+        source = cause.source();
+    }
+    message
+}
+
 pub fn test(username: &str, password: &str) -> bool {
     let mailer = SmtpTransport::builder_dangerous("smtp.wondersgroup.com")
         .credentials(Credentials::new(username.to_owned(), password.to_owned()))
         .build();
     mailer.test_connection().unwrap_or_else(|e| {
-        eprintln!("Error connecting to SMTP server: {}", e);
+        log::error("连接 SMTP 服务器失败", &[("user", username), ("error", &full_error(&e))]);
         false
     })
-}
\ No newline at end of file
+}
+
+pub fn is_valid_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((user, domain)) => {
+            !user.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !domain.contains('@')
+                && !email.chars().any(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+/// 邮箱属于允许的域名（含子域名）时返回 true，`domains` 为空表示不限制
+pub fn domain_allowed(email: &str, domains: &[String]) -> bool {
+    if domains.is_empty() {
+        return true;
+    }
+    let Some((_, domain)) = email.trim().rsplit_once('@') else {
+        return false;
+    };
+    let domain = domain.to_lowercase();
+    domains.iter().any(|allowed| domain == *allowed || domain.ends_with(&format!(".{}", allowed)))
+}
+
+static LAST_SEND: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// 批量发送和单行发送共用：距上一封邮件不足 `interval` 时先等待
+pub fn throttle(interval: Duration) {
+    // 在锁内排好自己的发送时刻，等待时不占着锁
+    let at = {
+        let mut last = LAST_SEND.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let at = last.map_or(now, |last| (last + interval).max(now));
+        *last = Some(at);
+        at
+    };
+    let now = Instant::now();
+    if at > now {
+        thread::sleep(at - now);
+    }
+}
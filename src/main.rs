@@ -2,30 +2,65 @@ mod excel;
 mod mail;
 mod html;
 mod log;
+mod template;
+mod preview;
+mod search;
+mod sort;
+mod layout;
+mod export;
+mod history;
+mod log_panel;
+mod dedup;
+mod suppression;
+mod schedule;
+mod cli;
+mod viewport;
+mod domains;
 
+use std::env;
 use std::fs;
-use iced::widget::{button, checkbox, column, container, responsive, row, scrollable, text, text_input};
-use iced::{alignment, event, keyboard, theme, window, Application, Color, Command, Event, Length, Renderer, Settings, Size, Subscription, Theme};
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use iced::widget::{button, checkbox, column, container, mouse_area, pick_list, responsive, row, scrollable, text, text_input};
+use iced::{alignment, event, keyboard, mouse, theme, time, window, Application, Color, Command, Event, Length, Renderer, Settings, Size, Subscription, Theme};
 use iced::{Element};
 use iced::keyboard::key;
 use iced::widget::text_input::Id;
 use chrono::Local;
 use iced_table::table;
-use lettre::Transport;
 use lettre::transport::smtp::authentication::Credentials;
 use native_dialog::{MessageDialog, MessageType};
 use serde::{Deserialize, Serialize};
+use crate::dedup::DuplicateAction;
 use crate::html::generate_html;
 use crate::mail::send_mail;
 
-pub const SAVED_FILE: &'static str = "./auth.dll";
-pub const MAIL_FILE: &'static str = "./mail.dll";
+pub const SAVED_FILE: &str = "./auth.dll";
+pub const MAIL_FILE: &str = "./mail.dll";
+pub const LAYOUT_FILE: &str = "./layout.dll";
+pub const LOG_FILE: &str = "./log.dll";
+pub const HISTORY_FILE: &str = "./history.dll";
+pub const SUPPRESSION_FILE: &str = "./suppression.dll";
+pub const SCHEDULE_FILE: &str = "./schedule.dll";
+pub const DOMAINS_FILE: &str = "./domains.dll";
+
+// 表格最前面的"全选"和"发送状态"两列不是导入的数据
+pub const FIXED_COLUMNS: usize = 2;
+
+// 因禁止发送名单而跳过的行，跳过原因以此开头
+const SUPPRESSED: &str = "禁止发送";
+
+// 双击单元格时出现的编辑框
+const EDIT_CELL: &str = "edit_cell";
 
 
 #[derive(Debug)]
 enum Mailbox {
     Config(AuthState),
-    Main(State),
+    Main(Box<State>),
+    History(Box<HistoryState>),
 }
 
 #[derive(Debug, Clone)]
@@ -33,19 +68,90 @@ struct State {
     list: Vec<Tasks>,
     headers: Vec<Header>,
     search_value: String,
+    filtered: Vec<usize>,
+    page: Vec<Tasks>,
+    page_size: PageSize,
+    page_input: String,
+    status_filter: StatusFilter,
+    drag_column: Option<usize>,
+    last_click: Option<(usize, usize, Instant)>,
     header: scrollable::Id,
     body: scrollable::Id,
+    /// 全部显示时自己绘制的表格内容，以及它的纵向滚动位置
+    rows: scrollable::Id,
+    row_offset: f32,
     title: String,
+    intro: String,
     remark: String,
+    template_path: String,
+    template: String,
+    interval: u64,
+    test_address: String,
+    // 一次发送超过这个数量时需要再次确认，0 表示不需要
+    confirm_threshold: usize,
     auth: AuthState,
     send_message: String,
+    campaign: String,
+    started_at: String,
+    source: String,
+    source_hash: String,
     cur_page: usize,
+    preview: Option<usize>,
+    duplicates: usize,
+    keep_duplicates: bool,
+    suppression: Vec<suppression::Entry>,
+    /// 禁止发送名单读取失败的原因，修复并重新读取之前不能发送，也不能修改名单
+    suppression_error: Option<String>,
+    show_suppression: bool,
+    row_menu: Option<usize>,
+    suppress_reason: String,
+    // 用户已在确认框中同意发往允许域名以外的地址
+    allow_external: bool,
+    scheduled: Option<schedule::Scheduled>,
+    // schedule.dll 无法读取时的错误，修复并重新读取前不能安排或修改定时发送
+    schedule_error: Option<String>,
+    /// 到时间后没有确认发送，暂停自动发送，等用户点击"现在发送"或改期
+    schedule_paused: bool,
+    schedule_input: String,
+    show_log: bool,
+    log_level: log::Level,
+    log_entries: Vec<log::Entry>,
+    log_scroll: scrollable::Id,
+}
+
+#[derive(Debug)]
+struct HistoryState {
+    // 返回时恢复原来的发送界面
+    back: State,
+    campaigns: Vec<history::Campaign>,
+    selected: Option<usize>,
+    search: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 struct MailData {
     title: String,
+    intro: String,
     remark: String,
+    template: String,
+    interval: u64,
+    test_address: String,
+    confirm_threshold: usize,
+}
+
+impl Default for MailData {
+    fn default() -> Self {
+        MailData {
+            title: String::new(),
+            intro: String::new(),
+            remark: String::new(),
+            template: String::new(),
+            interval: 0,
+            test_address: String::new(),
+            confirm_threshold: 200,
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -53,22 +159,68 @@ struct AuthState {
     username: String,
     password: String,
     save: bool,
+    /// 允许的收件域名，逗号分隔，留空表示不限制。
+    /// 按账户保存在 domains.dll 中，不随密码保存；旧版本 auth.dll 中的值只读不写
+    #[serde(default, skip_serializing)]
+    allowed_domains: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone)]
 struct Header {
     name: String,
+    index: usize,
     width: f32,
     check: bool,
+    raw: bool,
+    sort: Option<bool>,
+    resize: Option<f32>,
+    editing: Option<(usize, String)>,
 }
 
 #[derive(Debug, Default, Clone)]
 struct Tasks {
+    id: usize,
     email: String,
     name: String,
     seq: i64,
     info: Vec<String>,
     status: bool,
+    edited: Vec<usize>,
+    send_status: SendStatus,
+    attempts: u32,
+    sent_at: String,
+    message_id: String,
+    duplicate: bool,
+    // 合并进来的同一邮箱的其它行，和本行一起发送
+    merged: Vec<Tasks>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+enum SendStatus {
+    #[default]
+    Pending,
+    Sending,
+    Sent,
+    Failed(String),
+    Skipped(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PageSize {
+    Rows(usize),
+    /// 不分页，只为滚动到可见范围内的行生成控件，见 [`viewport`]
+    All,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum StatusFilter {
+    #[default]
+    All,
+    Pending,
+    Sending,
+    Sent,
+    Failed,
+    Skipped,
 }
 
 
@@ -78,26 +230,99 @@ enum Message {
     Username(String),
     Password(String),
     Save(bool),
+    AllowedDomains(String),
     Login,
     Import,
+    PickTemplate,
+    ResetTemplate,
     Title(String),
+    Intro(String),
     Remark(String),
-    Send,
     SyncHeader(scrollable::AbsoluteOffset),
+    ScrollRows(scrollable::AbsoluteOffset),
+    Search(String),
+    FilterStatus(StatusFilter),
+    DragColumn(usize),
+    DropColumn(usize),
+    ResizeColumn(usize, f32),
+    ResizeEnd,
+    ClickCell(usize, usize),
+    EditCell(String),
+    CommitEdit,
     Enable(usize, bool),
     AllSelect(bool),
+    ShowColumn(usize, bool),
+    RawHtml(usize, bool),
     NextPage,
     PrevPage,
+    FirstPage,
+    LastPage,
+    PageInput(String),
+    JumpPage,
+    PageSize(PageSize),
+    ShowPreview,
+    PrevPreview,
+    NextPreview,
+    OpenPreview,
+    ClosePreview,
+    Export,
+    RetryFailed,
+    Interval(String),
+    ConfirmThreshold(String),
+    SendOne(usize),
+    TestAddress(String),
+    SendTest,
+    EndTest(Result<String, String>),
     BeginSend,
-    EndSend(Vec<Tasks>),
+    ScheduleInput(String),
+    ScheduleSend,
+    Reschedule,
+    CancelSchedule,
+    ReloadSchedule,
+    SendScheduled,
+    /// 发送记录按开始发送时的主题、模板和列生成，发送期间界面上的修改不会混进去
+    EndSend(Box<history::Campaign>, Vec<Tasks>),
+    OpenHistory,
+    CloseHistory,
+    SelectCampaign(usize),
+    HistorySearch(String),
+    ReopenCampaign(usize),
+    Duplicates(DuplicateAction),
+    ToggleSuppression,
+    ReloadSuppression,
+    ImportSuppression,
+    RemoveSuppression(usize),
+    RowMenu(usize),
+    CloseRowMenu,
+    SuppressReason(String),
+    SuppressRow,
+    ToggleLog,
+    LogLevel(log::Level),
+    OpenLogDir,
+    Tick,
     Event(Event),
-    Nop,
 }
 
-#[derive(Debug, Clone)]
-enum LoadError {}
+impl Message {
+    /// 会改变列表内容、筛选、搜索或分页的消息，处理完后需要重新生成当前页
+    fn changes_list(&self) -> bool {
+        matches!(self,
+            Message::Import | Message::Search(_) | Message::FilterStatus(_) | Message::DropColumn(_)
+            | Message::CommitEdit | Message::Enable(..) | Message::AllSelect(_) | Message::ShowColumn(..)
+            | Message::NextPage | Message::PrevPage | Message::FirstPage | Message::LastPage
+            | Message::JumpPage | Message::PageSize(_)
+            | Message::RetryFailed | Message::SendOne(_) | Message::BeginSend | Message::EndSend(..)
+            | Message::Duplicates(_) | Message::ReloadSuppression | Message::ImportSuppression | Message::RemoveSuppression(_) | Message::SuppressRow)
+    }
+}
 
 fn main() -> iced::Result {
+    log::init(LOG_FILE);
+    // 以子命令运行时不打开界面，供定时任务等脚本调用
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| cli::is_command(arg)) {
+        process::exit(cli::run(&args));
+    }
     Mailbox::run(Settings {
         window: window::Settings {
             size: Size::new(1600.0, 800.0),
@@ -107,13 +332,37 @@ fn main() -> iced::Result {
     })
 }
 
+/// SMTP 连接和发送间隔的等待都会阻塞，放到专门的线程里执行，不占用界面的异步运行时
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> Result<T, String> {
+    tokio::task::spawn_blocking(f).await.map_err(|e| format!("发送线程异常退出: {}", e))
+}
+
+// 发送线程异常退出时，把还没有结果的行标记为失败，免得一直停在"发送中"
+fn failed(mut tasks: Vec<Tasks>, error: &str) -> Vec<Tasks> {
+    for task in &mut tasks {
+        task.send_status = SendStatus::Failed(error.to_owned());
+    }
+    tasks
+}
+
+/// 控件没有处理的事件，以及所有的松开鼠标事件：拖动表头后在任何地方松开都要结束拖动
+fn events() -> Subscription<Message> {
+    event::listen_with(|event, status| match (&event, status) {
+        (_, event::Status::Ignored)
+        | (Event::Mouse(mouse::Event::ButtonReleased(_)), _)
+        // 编辑框获得焦点后会吃掉 Esc，这里照样转发，用来取消编辑
+        | (Event::Keyboard(keyboard::Event::KeyPressed { key: keyboard::Key::Named(key::Named::Escape), .. }), _) => Some(Message::Event(event)),
+        _ => None,
+    })
+}
+
 impl Application for Mailbox {
     type Executor = iced::executor::Default;
     type Message = Message;
     type Theme = Theme;
     type Flags = ();
 
-    fn new(flags: Self::Flags) -> (Self, Command<Self::Message>) {
+    fn new(_flags: Self::Flags) -> (Self, Command<Self::Message>) {
         (
             Self::Config(AuthState {
                 save: true,
@@ -124,13 +373,27 @@ impl Application for Mailbox {
     }
 
     fn subscription(&self) -> Subscription<Self::Message> {
-        event::listen().map(Message::Event)
+        // 日志面板展开时定时刷新，发送过程中也能看到最新日志；有定时发送时用来倒计时，查看发送记录时也不停
+        let ticks = match self {
+            Mailbox::Main(state) => state.ticks(),
+            Mailbox::History(state) => state.back.ticks(),
+            Mailbox::Config(_) => false,
+        };
+        if ticks {
+            Subscription::batch(vec![
+                events(),
+                time::every(Duration::from_millis(500)).map(|_| Message::Tick),
+            ])
+        } else {
+            events()
+        }
     }
 
     fn title(&self) -> String {
         match self {
             Mailbox::Config(_) => "邮件配置信息".to_owned(),
             Mailbox::Main(_) => "邮件发送界面".to_owned(),
+            Mailbox::History(_) => "发送记录".to_owned(),
         }
     }
 
@@ -150,7 +413,13 @@ impl Application for Mailbox {
                     Message::Save(save) => {
                         state.save = save;
                     }
-                    Message::Login => {
+                    Message::AllowedDomains(value) => {
+                        state.allowed_domains = value;
+                    }
+                    Message::Login | Message::Event(Event::Keyboard(keyboard::Event::KeyPressed {
+                        key: keyboard::Key::Named(key::Named::Enter),
+                        ..
+                    })) => {
                         if state.username.is_empty() || state.password.is_empty() {
                             return Command::none();
                         }
@@ -163,144 +432,85 @@ impl Application for Mailbox {
                                 .unwrap();
                             return Command::none();
                         }
-
-                        if state.save {
-                            let data = serde_json::to_string(&state).unwrap();
-                            fs::write(SAVED_FILE, &data).unwrap();
-                        } else {
-                            fs::remove_file(SAVED_FILE).unwrap();
-                        }
-
-                        let mail_data = read_mail_data();
-                        *self = Mailbox::Main(State {
-                            list: vec! {},
-                            headers: vec![],
-                            search_value: "".to_owned(),
-                            header: scrollable::Id::unique(),
-                            body: scrollable::Id::unique(),
-                            remark: mail_data.remark,
-                            title: mail_data.title,
-                            auth: state.clone(),
-                            cur_page: 0,
-                            send_message: String::new(),
-                        });
-                    }
-                    Message::Event(event) => match event {
-                        Event::Keyboard(keyboard::Event::KeyPressed {
-                                            key: keyboard::Key::Named(key::Named::Enter),
-                                            ..
-                                        }) => {
-                            if state.username.is_empty() || state.password.is_empty() {
-                                return Command::none();
-                            }
-                            if !mail::test(&state.username, &state.password) {
+                        // 域名设置读不出来时不能当作不限制继续登录
+                        match domains::resolve(&state.username, &state.allowed_domains) {
+                            Ok(domains) => state.allowed_domains = domains,
+                            Err(e) => {
                                 MessageDialog::new()
                                     .set_type(MessageType::Error)
                                     .set_title("登陆提示")
-                                    .set_text("账户名或密码有误，登陆失败！")
+                                    .set_text(&e)
                                     .show_alert()
                                     .unwrap();
                                 return Command::none();
                             }
+                        }
 
-                            if state.save {
-                                let data = serde_json::to_string(&state).unwrap();
-                                fs::write(SAVED_FILE, &data).unwrap();
-                            } else {
-                                fs::remove_file(SAVED_FILE).unwrap();
-                            }
-
-                            let mail_data = read_mail_data();
-                            *self = Mailbox::Main(State {
-                                list: vec! {},
-                                headers: vec![],
-                                search_value: "".to_owned(),
-                                header: scrollable::Id::unique(),
-                                body: scrollable::Id::unique(),
-                                remark: mail_data.remark,
-                                title: mail_data.title,
-                                auth: state.clone(),
-                                cur_page: 0,
-                                send_message: String::new(),
-                            });
+                        if state.save {
+                            let data = serde_json::to_string(&state).unwrap();
+                            fs::write(SAVED_FILE, &data).unwrap();
+                        } else {
+                            fs::remove_file(SAVED_FILE).unwrap();
                         }
-                        _ => {}
+
+                        *self = Mailbox::Main(Box::new(State::new(state.clone(), read_mail_data())));
                     }
                     _ => {}
                 }
             }
             Mailbox::Main(state) => {
-                match message {
-                    Message::Title(value) => {
-                        state.title = value.trim().to_owned();
-                    }
-                    Message::Remark(value) => {
-                        state.remark = value.trim().to_owned();
-                    }
-                    Message::Import => {
-                        let file = rfd::FileDialog::new()
-                            .add_filter("excel files (*.xlsx)", &["xlsx", "xls"])
-                            .set_directory("/")
-                            .pick_file();
-                        if let Some(path) = file {
-                            let (list, headers) = excel::parse_excel(path);
-                            state.list = list;
-                            state.headers = headers;
+                if let Message::OpenHistory = message {
+                    let mut campaigns = match history::load() {
+                        Ok(campaigns) => campaigns,
+                        Err(e) => {
+                            MessageDialog::new()
+                                .set_type(MessageType::Error)
+                                .set_title("发送记录")
+                                .set_text(&e)
+                                .show_alert()
+                                .unwrap();
+                            return Command::none();
                         }
+                    };
+                    // 最近的发送排在最前面
+                    campaigns.reverse();
+                    *self = Mailbox::History(Box::new(HistoryState {
+                        back: (**state).clone(),
+                        selected: if campaigns.is_empty() { None } else { Some(0) },
+                        campaigns,
+                        search: String::new(),
+                    }));
+                    return Command::none();
+                }
+                return State::update_and_refresh(state, message);
+            }
+            Mailbox::History(state) => {
+                match message {
+                    Message::SelectCampaign(index) => {
+                        state.selected = Some(index);
                     }
-                    Message::Enable(row_index, enable) => {
-                        state.list[row_index].status = enable;
-                    }
-                    Message::AllSelect(enable) => {
-                        state.list.iter_mut().for_each(|task| task.status = enable);
-                        state.headers.iter_mut().for_each(|header| header.check = enable);
+                    Message::HistorySearch(value) => {
+                        state.search = value;
                     }
-                    Message::SyncHeader(offset) => {
-                        return Command::batch(vec![
-                            scrollable::scroll_to(state.header.clone(), offset)
-                        ])
+                    Message::CloseHistory => {
+                        *self = Mailbox::Main(Box::new(state.back.clone()));
                     }
-                    Message::BeginSend => {
-                        if state.list.is_empty() {
-                            return Command::none();
-                        }
-                        let yes = MessageDialog::new()
+                    Message::ReopenCampaign(_) if state.back.sending() => {
+                        MessageDialog::new()
                             .set_type(MessageType::Info)
-                            .set_title("发送确认")
-                            .set_text("是否确认发送?")
-                            .show_confirm()
+                            .set_title("发送记录")
+                            .set_text("还有邮件正在发送，请等待发送结束后再重新打开")
+                            .show_alert()
                             .unwrap();
-                        if !yes {
-                            return Command::none();
-                        }
-                        state.send_message = "发送邮件中...".to_owned();
-
-                        return Command::perform(State::send(state.clone()), Message::EndSend);
                     }
-                    Message::EndSend(tasks) => {
-                        if tasks.is_empty() {
-                            state.send_message = "发送完毕".to_string();
-                        } else {
-                            state.send_message = format!("发送完毕，剩余{}条邮件未发送成功", tasks.len());
-                        }
-                        state.list = tasks;
-
-                        set_mail_data(&MailData {
-                            remark: state.remark.clone(),
-                            title: state.title.clone(),
-                        });
-                    }
-                    Message::PrevPage => {
-                        if state.cur_page > 0 {
-                            state.cur_page -= 1;
-                        }
-                    }
-                    Message::NextPage => {
-                        if state.cur_page + 1 < (state.list.len() + 49) / 50 {
-                            state.cur_page += 1;
-                        }
+                    Message::ReopenCampaign(index) => {
+                        let mut back = state.back.clone();
+                        back.reopen(&state.campaigns[index]);
+                        back.refresh();
+                        *self = Mailbox::Main(Box::new(back));
                     }
-                    _ => {}
+                    // 发送结果、定时发送等仍然交给主界面处理，返回时列表是最新的
+                    message => return State::update_and_refresh(&mut state.back, message),
                 }
             }
         }
@@ -330,6 +540,11 @@ impl Application for Mailbox {
                     .padding(30)
                     .size(20);
 
+                let allowed_domains = text_input("允许的收件域名，多个用逗号分隔，留空沿用上次的设置", &state.allowed_domains)
+                    .on_input(Message::AllowedDomains)
+                    .padding(30)
+                    .size(20);
+
                 let password_check = checkbox("保存密码", state.save)
                     .on_toggle(Message::Save);
 
@@ -338,7 +553,7 @@ impl Application for Mailbox {
                     .on_press(Message::Login)
                     .style(theme::Button::Primary)).width(Length::Fill).center_y().center_x();
 
-                let content = column![title, username, password, password_check, btn]
+                let content = column![title, username, password, allowed_domains, password_check, btn]
                     .spacing(20)
                     .max_width(500);
 
@@ -346,20 +561,72 @@ impl Application for Mailbox {
             }
             Mailbox::Main(state) => {
                 let import_button = button("导入Excel").padding([5, 10])
-                    .on_press(Message::Import)
+                    .on_press_maybe((!state.sending()).then_some(Message::Import))
                     .style(theme::Button::Primary);
 
+                let preview_button = button("预览").padding([5, 10])
+                    .on_press(Message::ShowPreview)
+                    .style(theme::Button::Secondary);
+
                 let send_button = button("发送邮件").padding([5, 10])
-                    .on_press(Message::BeginSend)
+                    .on_press_maybe((!state.sending()).then_some(Message::BeginSend))
                     .style(theme::Button::Primary);
 
+                let test_button = button("测试发送").padding([5, 10])
+                    .on_press(Message::SendTest)
+                    .style(theme::Button::Secondary);
+                let test_address = text_input("测试收件地址，默认发给自己", &state.test_address)
+                    .on_input(Message::TestAddress)
+                    .padding(5)
+                    .width(250);
+
+                let export_button = button("导出结果").padding([5, 10])
+                    .on_press(Message::Export)
+                    .style(theme::Button::Secondary);
+
+                let history_button = button("发送记录").padding([5, 10])
+                    .on_press(Message::OpenHistory)
+                    .style(theme::Button::Secondary);
+
+                let suppression_button = button("禁止发送名单").padding([5, 10])
+                    .on_press(Message::ToggleSuppression)
+                    .style(theme::Button::Secondary);
+
+                let allowed_domains = text_input("允许的收件域名，留空不限制", &state.auth.allowed_domains)
+                    .on_input(Message::AllowedDomains)
+                    .padding(5)
+                    .width(250);
+
+                let log_button = button(if state.show_log { "收起日志" } else { "查看日志" }).padding([5, 10])
+                    .on_press(Message::ToggleLog)
+                    .style(theme::Button::Secondary);
+
                 let send_info = if state.send_message.is_empty() {
                     text("")
                 } else {
                     text(&state.send_message)
                 };
                 let title = row![text_input("邮件主题配置", &state.title).on_input(Message::Title).padding(10).size(20).width(1100), import_button].spacing(20);
-                let remark = row![text_input("邮件提示信息", &state.remark).on_input(Message::Remark).padding(10).size(20).width(1100), send_button, send_info].spacing(20);
+                let template_button = button("选择模板").padding([5, 10])
+                    .on_press(Message::PickTemplate)
+                    .style(theme::Button::Secondary);
+                let reset_button = button("默认模板").padding([5, 10])
+                    .on_press(Message::ResetTemplate)
+                    .style(theme::Button::Secondary);
+                let template_name = if state.template_path.is_empty() {
+                    text("当前模板: 默认模板")
+                } else {
+                    text(format!("当前模板: {}", state.template_path))
+                };
+                let intro = row![text_input("邮件引言，可使用 {{列名}} 引用表格数据", &state.intro).on_input(Message::Intro).padding(10).size(20).width(1100), template_button, reset_button, template_name].spacing(20);
+                let remark = row![text_input("邮件提示信息", &state.remark).on_input(Message::Remark).padding(10).size(20).width(1100), preview_button, test_button, send_button, export_button, history_button, send_info].spacing(20);
+
+                let missing = state.missing_columns();
+                let template_info = if missing.is_empty() {
+                    text("")
+                } else {
+                    text(format!("模板引用了不存在的列: {}", missing.join("、"))).style(Color::from([0.8, 0.2, 0.2]))
+                };
 
                 let prev_button = button("上一页").padding([5, 10])
                     .on_press(Message::PrevPage)
@@ -369,31 +636,153 @@ impl Application for Mailbox {
                     .on_press(Message::NextPage)
                     .style(theme::Button::Secondary);
 
-                let page_info = text(format!("第 {}/{} 页", state.cur_page + 1, (state.list.len() + 49) / 50));
-                let page_buttons = row![prev_button, next_button, page_info].spacing(20);
+                let first_button = button("首页").padding([5, 10])
+                    .on_press(Message::FirstPage)
+                    .style(theme::Button::Secondary);
+
+                let last_button = button("末页").padding([5, 10])
+                    .on_press(Message::LastPage)
+                    .style(theme::Button::Secondary);
+
+                let jump = text_input("页码", &state.page_input)
+                    .on_input(Message::PageInput)
+                    .on_submit(Message::JumpPage)
+                    .padding(5)
+                    .width(60);
+                let jump_button = button("跳转").padding([5, 10])
+                    .on_press(Message::JumpPage)
+                    .style(theme::Button::Secondary);
+                let page_size = pick_list(&PageSize::OPTIONS[..], Some(state.page_size), Message::PageSize);
+
+                let selected = state.list.iter().filter(|task| task.status).count();
+                let page_info = text(format!("第 {}/{} 页，共 {} 条（筛选后 {} 条），已选 {} 条",
+                                             state.cur_page + 1, state.page_count().max(1),
+                                             state.list.len(), state.filtered.len(), selected));
+                let search = text_input("搜索邮箱、姓名或任意列，按列搜索用 列名:值", &state.search_value)
+                    .on_input(Message::Search)
+                    .padding(5)
+                    .width(400);
+                let status_filter = pick_list(&StatusFilter::ALL[..], Some(state.status_filter), Message::FilterStatus);
+                let retry_button = button("重试失败").padding([5, 10])
+                    .on_press_maybe((!state.sending()).then_some(Message::RetryFailed))
+                    .style(theme::Button::Secondary);
+                let interval = row![
+                    text("发送间隔(毫秒)"),
+                    text_input("0", &state.interval.to_string()).on_input(Message::Interval).padding(5).width(80),
+                    text("超过多少封需再次确认"),
+                    text_input("0", &state.confirm_threshold.to_string()).on_input(Message::ConfirmThreshold).padding(5).width(60),
+                ].spacing(5).align_items(alignment::Alignment::Center);
+                let page_buttons = row![first_button, prev_button, next_button, last_button, jump, jump_button, page_size, page_info]
+                    .spacing(10).align_items(alignment::Alignment::Center);
+                let tools = row![search, status_filter, retry_button, interval, test_address, allowed_domains, suppression_button, log_button].spacing(20).align_items(alignment::Alignment::Center);
 
                 let table: Element<_> = if state.list.is_empty() {
                     empty_message("请先导入Excel数据!")
+                } else if state.filtered.is_empty() {
+                    empty_message("没有符合搜索条件的数据")
                 } else {
-                    let next_index = (state.cur_page + 1) * 50;
-                    let next_index = if next_index > state.list.len() {
-                        state.list.len()
-                    } else {
-                        next_index
-                    };
                     responsive(move |size| {
-                        table(
+                        let table = table(
                             state.header.clone(),
                             state.body.clone(),
                             &state.headers,
-                            &state.list[state.cur_page * 50..next_index],
+                            &state.page,
                             Message::SyncHeader,
-                        ).into()
+                        ).on_column_resize(Message::ResizeColumn, Message::ResizeEnd)
+                            .min_column_width(viewport::MIN_COLUMN_WIDTH);
+                        if state.page_size == PageSize::All {
+                            // 表格只用来显示表头，内容由 viewport 按滚动位置生成
+                            column![
+                                container(table).height(viewport::HEADER_HEIGHT),
+                                viewport::view(state, size.height - viewport::HEADER_HEIGHT),
+                            ].into()
+                        } else {
+                            table.into()
+                        }
                     }).into()
                 };
 
-                let content = column![title, remark, page_buttons, table]
+                let table: Element<_> = match state.preview.and_then(|id| state.position(id)) {
+                    Some(index) => row![table, preview::view(state, index)].spacing(10).into(),
+                    None => table,
+                };
+
+                let duplicates: Element<_> = if state.duplicates > 0 && !state.keep_duplicates {
+                    row![
+                        text(format!("发现 {} 个重复的邮箱地址，已用紫色标出", state.duplicates)).style(Color::from([0.6, 0.2, 0.7])),
+                        button("只保留第一条").padding([5, 10]).on_press(Message::Duplicates(DuplicateAction::KeepFirst)).style(theme::Button::Secondary),
+                        button("全部保留").padding([5, 10]).on_press(Message::Duplicates(DuplicateAction::KeepAll)).style(theme::Button::Secondary),
+                        button("合并为一封邮件").padding([5, 10]).on_press(Message::Duplicates(DuplicateAction::Merge)).style(theme::Button::Secondary),
+                    ].spacing(10).align_items(alignment::Alignment::Center).into()
+                } else {
+                    text("").into()
+                };
+
+                // 右键单元格弹出的行操作
+                let row_menu: Element<_> = match state.row_menu.and_then(|id| state.position(id)) {
+                    Some(index) => row![
+                        text(format!("{}：", state.list[index].email)),
+                        button("发送这一行").padding([5, 10]).on_press_maybe((!state.sending()).then_some(Message::SendOne(state.list[index].id))).style(theme::Button::Secondary),
+                        text_input("加入禁止发送名单的原因", &state.suppress_reason).on_input(Message::SuppressReason).on_submit(Message::SuppressRow).padding(5).width(300),
+                        button("加入禁止发送名单").padding([5, 10]).on_press(Message::SuppressRow).style(theme::Button::Destructive),
+                        button("取消").padding([5, 10]).on_press(Message::CloseRowMenu).style(theme::Button::Secondary),
+                    ].spacing(10).align_items(alignment::Alignment::Center).into(),
+                    None => text("").into(),
+                };
+
+                let schedule_input = text_input("定时发送时间，如 09:00 或 2024-01-02 09:00", &state.schedule_input)
+                    .on_input(Message::ScheduleInput)
+                    .padding(5)
+                    .width(300);
+                let schedule: Element<_> = match (&state.scheduled, &state.schedule_error) {
+                    (_, Some(e)) => row![
+                        text(format!("{}，修复前不能安排定时发送", e)).style(Color::from([0.8, 0.2, 0.2])),
+                        button("重新读取").padding([5, 10]).on_press(Message::ReloadSchedule).style(theme::Button::Secondary),
+                    ].spacing(10).align_items(alignment::Alignment::Center).into(),
+                    (Some(scheduled), None) => {
+                        let remaining = match (scheduled.remaining(), scheduled.due_at()) {
+                            (Some(remaining), _) => format!("还剩 {:02}:{:02}:{:02}", remaining.num_hours(),
+                                                            remaining.num_minutes() % 60, remaining.num_seconds() % 60),
+                            (None, None) => "发送时间无效，请改期".to_owned(),
+                            (None, Some(_)) if state.schedule_paused => "已暂停，等待确认".to_owned(),
+                            (None, Some(_)) => "即将发送".to_owned(),
+                        };
+                        let mut schedule = row![
+                            text(format!("「{}」共 {} 封邮件将于 {} 发送，{}", scheduled.title, scheduled.rows.len(), scheduled.at, remaining))
+                                .style(Color::from([0.2, 0.4, 0.8])),
+                            schedule_input,
+                            button("改期").padding([5, 10]).on_press(Message::Reschedule).style(theme::Button::Secondary),
+                            button("取消定时").padding([5, 10]).on_press(Message::CancelSchedule).style(theme::Button::Destructive),
+                        ].spacing(10).align_items(alignment::Alignment::Center);
+                        if state.schedule_paused {
+                            schedule = schedule.push(button("现在发送").padding([5, 10])
+                                .on_press_maybe((!state.sending()).then_some(Message::SendScheduled))
+                                .style(theme::Button::Primary));
+                        }
+                        schedule.into()
+                    }
+                    (None, None) => row![
+                        schedule_input,
+                        button("定时发送").padding([5, 10]).on_press(Message::ScheduleSend).style(theme::Button::Secondary),
+                    ].spacing(10).align_items(alignment::Alignment::Center).into(),
+                };
+
+                let suppression_error: Element<_> = match &state.suppression_error {
+                    Some(e) => row![
+                        text(format!("{}，修复前不能发送邮件", e)).style(Color::from([0.8, 0.2, 0.2])),
+                        button("重新读取").padding([5, 10]).on_press(Message::ReloadSuppression).style(theme::Button::Secondary),
+                    ].spacing(10).align_items(alignment::Alignment::Center).into(),
+                    None => text("").into(),
+                };
+
+                let mut content = column![title, intro, remark, template_info, suppression_error, duplicates, tools, schedule, page_buttons, row_menu, table]
                     .spacing(10);
+                if state.show_suppression {
+                    content = content.push(suppression::view(state));
+                }
+                if state.show_log {
+                    content = content.push(log_panel::view(state));
+                }
 
                 // scrollable(container(content).center_x(Fill).padding(40)).into()
                 container(container(content).width(Length::Fill).height(Length::Fill))
@@ -404,6 +793,7 @@ impl Application for Mailbox {
                     .center_y()
                     .into()
             }
+            Mailbox::History(state) => history::view(state),
         }
     }
 }
@@ -422,6 +812,14 @@ fn empty_message(message: &str) -> Element<'_, Message> {
 }
 
 impl AuthState {
+    fn domains(&self) -> Vec<String> {
+        self.allowed_domains
+            .split([',', '，', ';', ' '])
+            .map(|domain| domain.trim().trim_start_matches('@').to_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .collect()
+    }
+
     async fn load() -> AuthState {
         match fs::read_to_string(SAVED_FILE) {
             Ok(data) => serde_json::from_str(&data).unwrap(),
@@ -431,62 +829,1410 @@ impl AuthState {
 }
 
 impl State {
-    async fn send(state: State) -> Vec<Tasks> {
-        let creds = Credentials::new(state.auth.username.clone(), state.auth.password.clone());
-        let mut failed_task = vec![];
-        for task in state.list {
-            if task.status {
-                log::info(&get_info(&task, "开始生成邮件 html", "INFO"));
-                let html = generate_html(&task, &state.headers, &state.remark);
-                log::info(&get_info(&task, "生成邮件 html 成功", "INFO"));
-                match send_mail(&format!("[{}]{}", &task.name, state.title), &html,
-                                &format!("{}@wondersgroup.com", state.auth.username),
-                                &task.email, creds.clone()) {
-                    Ok(_) => { log::info(&get_info(&task, "邮件发送成功", "INFO")) }
+    fn new(auth: AuthState, mail_data: MailData) -> State {
+        let (suppression, suppression_error) = match suppression::load() {
+            Ok(entries) => (entries, None),
+            Err(e) => {
+                log::error("读取禁止发送名单失败", &[("error", &e)]);
+                (vec![], Some(e))
+            }
+        };
+        let (scheduled, schedule_error) = match schedule::load() {
+            Ok(scheduled) => (scheduled, None),
+            Err(e) => {
+                log::error("读取定时发送失败", &[("error", &e)]);
+                (None, Some(e))
+            }
+        };
+        let (template_path, template) = match html::load_template(&mail_data.template) {
+            Ok(template) => (mail_data.template, template),
+            Err(e) => {
+                log::warn("加载模板失败，改用默认模板", &[("error", &e)]);
+                (String::new(), html::DEFAULT_TEMPLATE.to_owned())
+            }
+        };
+        State {
+            list: vec! {},
+            headers: vec![],
+            search_value: "".to_owned(),
+            filtered: vec![],
+            page: vec![],
+            page_size: PageSize::Rows(50),
+            page_input: String::new(),
+            status_filter: StatusFilter::All,
+            drag_column: None,
+            last_click: None,
+            header: scrollable::Id::unique(),
+            body: scrollable::Id::unique(),
+            rows: scrollable::Id::unique(),
+            row_offset: 0.0,
+            remark: mail_data.remark,
+            intro: mail_data.intro,
+            title: mail_data.title,
+            template_path,
+            template,
+            interval: mail_data.interval,
+            test_address: mail_data.test_address,
+            confirm_threshold: mail_data.confirm_threshold,
+            auth,
+            cur_page: 0,
+            send_message: String::new(),
+            campaign: String::new(),
+            started_at: String::new(),
+            source: String::new(),
+            source_hash: String::new(),
+            preview: None,
+            duplicates: 0,
+            keep_duplicates: false,
+            suppression,
+            suppression_error,
+            show_suppression: false,
+            row_menu: None,
+            suppress_reason: String::new(),
+            allow_external: false,
+            scheduled,
+            schedule_error,
+            schedule_paused: false,
+            schedule_input: String::new(),
+            show_log: false,
+            log_level: log::Level::Info,
+            log_entries: vec![],
+            log_scroll: scrollable::Id::unique(),
+        }
+    }
+
+    fn update_and_refresh(state: &mut State, message: Message) -> Command<Message> {
+        let changes_list = message.changes_list();
+        let command = State::update(state, message);
+        if changes_list {
+            state.refresh();
+        }
+        command
+    }
+
+    fn update(state: &mut State, message: Message) -> Command<Message> {
+        match message {
+            Message::Title(value) => {
+                state.title = value.trim().to_owned();
+            }
+            Message::Intro(value) => {
+                state.intro = value.trim().to_owned();
+            }
+            Message::Interval(value) => {
+                if let Ok(interval) = value.trim().parse() {
+                    state.interval = interval;
+                } else if value.trim().is_empty() {
+                    state.interval = 0;
+                }
+            }
+            Message::ConfirmThreshold(value) => {
+                if let Ok(threshold) = value.trim().parse() {
+                    state.confirm_threshold = threshold;
+                } else if value.trim().is_empty() {
+                    state.confirm_threshold = 0;
+                }
+            }
+            Message::Remark(value) => {
+                state.remark = value.trim().to_owned();
+            }
+            // 新导入的行 id 从 0 开始，发送结果回来时会对到别的行上
+            Message::Import if state.sending() => {
+                MessageDialog::new()
+                    .set_type(MessageType::Info)
+                    .set_title("导入提示")
+                    .set_text("还有邮件正在发送，请等待发送结束后再导入")
+                    .show_alert()
+                    .unwrap();
+            }
+            Message::Import => {
+                let file = rfd::FileDialog::new()
+                    .add_filter("excel files (*.xlsx)", &["xlsx", "xls"])
+                    .set_directory("/")
+                    .pick_file();
+                if let Some(path) = file {
+                    let source = path.to_string_lossy().to_string();
+                    let source_hash = history::file_hash(&path);
+                    let (list, mut headers) = match excel::parse_excel(path, "Sheet1", &excel::Mapping::default()) {
+                        Ok(data) => data,
+                        Err(e) => {
+                            MessageDialog::new()
+                                .set_type(MessageType::Error)
+                                .set_title("导入提示")
+                                .set_text(&e)
+                                .show_alert()
+                                .unwrap();
+                            return Command::none();
+                        }
+                    };
+                    state.source = source;
+                    state.source_hash = source_hash;
+                    layout::apply(&mut headers);
+                    state.list = list;
+                    state.mark_duplicates();
+                    if state.duplicates > 0 {
+                        log::warn("导入的数据中有重复的邮箱地址", &[("source", &state.source), ("count", &state.duplicates.to_string())]);
+                    }
+                    state.headers = headers;
+                    state.preview = None;
+                    state.keep_duplicates = false;
+                    state.row_menu = None;
+                    state.apply_suppression();
+                }
+            }
+            Message::PickTemplate => {
+                let file = rfd::FileDialog::new()
+                    .add_filter("html files (*.html)", &["html", "htm"])
+                    .pick_file();
+                if let Some(path) = file {
+                    let path = path.to_string_lossy().to_string();
+                    match html::load_template(&path) {
+                        Ok(template) => {
+                            state.template_path = path;
+                            state.template = template;
+                        }
+                        Err(e) => {
+                            MessageDialog::new()
+                                .set_type(MessageType::Error)
+                                .set_title("模板提示")
+                                .set_text(&e)
+                                .show_alert()
+                                .unwrap();
+                        }
+                    }
+                }
+            }
+            Message::ResetTemplate => {
+                state.template_path = String::new();
+                state.template = html::DEFAULT_TEMPLATE.to_owned();
+            }
+            Message::Search(value) => {
+                state.search_value = value;
+                state.cur_page = 0;
+            }
+            Message::DragColumn(col_index) => {
+                state.drag_column = Some(col_index);
+            }
+            // 在同一列上按下并松开视为点击排序，拖到其它列上松开则移动该列
+            Message::DropColumn(col_index) => {
+                match state.drag_column.take() {
+                    Some(from) if from == col_index => {
+                        let ascending = state.headers[col_index].sort != Some(true);
+                        state.headers.iter_mut().for_each(|header| header.sort = None);
+                        state.headers[col_index].sort = Some(ascending);
+                        let index = state.headers[col_index].index;
+                        let kind = sort::Kind::of(state.list.iter().map(|task| task.value(index)));
+                        state.list.sort_by(|a, b| {
+                            let ordering = sort::compare(kind, &a.value(index), &b.value(index));
+                            if ascending { ordering } else { ordering.reverse() }
+                        });
+                    }
+                    Some(from) => {
+                        let header = state.headers.remove(from);
+                        state.headers.insert(col_index, header);
+                        layout::save(&state.headers);
+                    }
+                    None => {}
+                }
+            }
+            // 表头上松开时已经先处理了 DropColumn，这里只清掉拖到表头以外松开的情况
+            Message::Event(Event::Mouse(mouse::Event::ButtonReleased(_))) => {
+                state.drag_column = None;
+            }
+            Message::ResizeColumn(col_index, offset) => {
+                state.headers[col_index].resize = Some(offset);
+            }
+            Message::ResizeEnd => {
+                for header in state.headers.iter_mut() {
+                    if let Some(offset) = header.resize.take() {
+                        header.width = (header.width + offset).max(30.0);
+                    }
+                }
+                layout::save(&state.headers);
+            }
+            Message::ClickCell(id, col_index) => {
+                let double_click = state.last_click
+                    .is_some_and(|(last_id, last_col, at)| last_id == id && last_col == col_index && at.elapsed() < Duration::from_millis(500));
+                if double_click {
+                    state.last_click = None;
+                    let index = state.headers[col_index].index;
+                    if let Some(position) = state.position(id) {
+                        let value = state.list[position].value(index);
+                        state.headers.iter_mut().for_each(|header| header.editing = None);
+                        state.headers[col_index].editing = Some((id, value));
+                        return text_input::focus(Id::new(EDIT_CELL));
+                    }
+                } else {
+                    state.last_click = Some((id, col_index, Instant::now()));
+                    if state.preview.is_some() || state.headers[col_index].index == 0 {
+                        state.preview = Some(id);
+                    }
+                }
+            }
+            Message::EditCell(value) => {
+                if let Some((_, editing)) = state.headers.iter_mut().find_map(|header| header.editing.as_mut()) {
+                    *editing = value;
+                }
+            }
+            Message::CommitEdit => {
+                let Some(col_index) = state.headers.iter().position(|header| header.editing.is_some()) else {
+                    return Command::none();
+                };
+                let index = state.headers[col_index].index;
+                let (id, value) = state.headers[col_index].editing.clone().unwrap();
+                let value = value.trim().to_owned();
+                let error = if index == 0 && !mail::is_valid_email(&value) {
+                    Some(format!("邮箱地址格式不正确: {}", value))
+                } else if index == 1 && value.parse::<i64>().is_err() {
+                    Some(format!("序号必须是整数: {}", value))
+                } else {
+                    None
+                };
+                if let Some(error) = error {
+                    MessageDialog::new()
+                        .set_type(MessageType::Error)
+                        .set_title("编辑提示")
+                        .set_text(&error)
+                        .show_alert()
+                        .unwrap();
+                    return Command::none();
+                }
+                state.headers[col_index].editing = None;
+                if let Some(task) = state.task_mut(id) {
+                    if task.value(index) != value {
+                        task.set_value(index, value);
+                        if !task.edited.contains(&index) {
+                            task.edited.push(index);
+                        }
+                    }
+                }
+                if index == 0 {
+                    state.mark_duplicates();
+                }
+            }
+            Message::Event(Event::Keyboard(keyboard::Event::KeyPressed {
+                key: keyboard::Key::Named(key::Named::Escape),
+                ..
+            })) => {
+                state.headers.iter_mut().for_each(|header| header.editing = None);
+            }
+            Message::FilterStatus(filter) => {
+                state.status_filter = filter;
+                state.cur_page = 0;
+            }
+            Message::Enable(id, enable) => {
+                if let Some(task) = state.task_mut(id) {
+                    task.status = enable;
+                }
+            }
+            Message::AllSelect(enable) => {
+                for index in &state.filtered {
+                    state.list[*index].status = enable;
+                }
+                state.headers[0].check = enable;
+            }
+            Message::ShowColumn(col_index, show) => {
+                state.headers[col_index].check = show;
+            }
+            Message::RawHtml(col_index, raw) => {
+                state.headers[col_index].raw = raw;
+            }
+            Message::ShowPreview if !state.list.is_empty() => {
+                let task = state.list.iter().find(|task| task.status).unwrap_or(&state.list[0]);
+                state.preview = Some(task.id);
+            }
+            Message::PrevPreview => {
+                if let Some(index) = state.preview.and_then(|id| state.position(id)) {
+                    state.preview = Some(state.list[index.saturating_sub(1)].id);
+                }
+            }
+            Message::NextPreview => {
+                if let Some(index) = state.preview.and_then(|id| state.position(id)) {
+                    state.preview = Some(state.list[(index + 1).min(state.list.len() - 1)].id);
+                }
+            }
+            Message::OpenPreview => {
+                if let Some(index) = state.preview.and_then(|id| state.position(id)) {
+                    let (subject, html) = state.render(&state.list[index]);
+                    if let Err(e) = preview::open_in_browser(&subject, &html) {
+                        MessageDialog::new()
+                            .set_type(MessageType::Error)
+                            .set_title("预览提示")
+                            .set_text(&e)
+                            .show_alert()
+                            .unwrap();
+                    }
+                }
+            }
+            Message::ClosePreview => {
+                state.preview = None;
+            }
+            Message::SyncHeader(offset) => {
+                return Command::batch(vec![
+                    scrollable::scroll_to(state.header.clone(), offset)
+                ])
+            }
+            Message::BeginSend => {
+                if state.sending() || !state.confirm_send("是否确认发送?") {
+                    return Command::none();
+                }
+                return state.start_send();
+            }
+            Message::ScheduleInput(value) => {
+                state.schedule_input = value;
+            }
+            // 原来的定时任务读不出来，这时保存会把它覆盖掉
+            Message::ScheduleSend | Message::Reschedule | Message::CancelSchedule if state.schedule_error.is_some() => {}
+            Message::ScheduleSend => {
+                let at = match schedule::parse_time(&state.schedule_input) {
+                    Ok(at) => at,
                     Err(e) => {
-                        failed_task.push(task.clone());
-                        log::info(&get_info(&task, &format!("发送失败: {}", e), "ERROR"))
+                        MessageDialog::new()
+                            .set_type(MessageType::Error)
+                            .set_title("定时发送")
+                            .set_text(&e)
+                            .show_alert()
+                            .unwrap();
+                        return Command::none();
+                    }
+                };
+                if state.scheduled.is_some() {
+                    let yes = MessageDialog::new()
+                        .set_type(MessageType::Warning)
+                        .set_title("定时发送")
+                        .set_text("已有一批等待定时发送的邮件，是否用当前数据替换?")
+                        .show_confirm()
+                        .unwrap();
+                    if !yes {
+                        return Command::none();
+                    }
+                }
+                if !state.confirm_send(&format!("是否确认在 {} 发送?", at)) {
+                    return Command::none();
+                }
+                let scheduled = state.schedule(at);
+                match schedule::save(&scheduled) {
+                    Ok(()) => {
+                        log::info("已安排定时发送", &[("at", &scheduled.at), ("title", &scheduled.title), ("count", &scheduled.rows.len().to_string())]);
+                        state.scheduled = Some(scheduled);
+                        state.schedule_paused = false;
+                        state.schedule_input.clear();
+                    }
+                    Err(e) => {
+                        MessageDialog::new()
+                            .set_type(MessageType::Error)
+                            .set_title("定时发送")
+                            .set_text(&e)
+                            .show_alert()
+                            .unwrap();
                     }
                 }
-            } else {
-                failed_task.push(task.clone());
+            }
+            Message::Reschedule => {
+                let Some(scheduled) = state.scheduled.as_mut() else {
+                    return Command::none();
+                };
+                match schedule::parse_time(&state.schedule_input).and_then(|at| {
+                    scheduled.at = at;
+                    schedule::save(scheduled)
+                }) {
+                    Ok(()) => {
+                        log::info("定时发送已改期", &[("at", &scheduled.at), ("title", &scheduled.title)]);
+                        state.schedule_paused = false;
+                        state.schedule_input.clear();
+                    }
+                    Err(e) => {
+                        MessageDialog::new()
+                            .set_type(MessageType::Error)
+                            .set_title("定时发送")
+                            .set_text(&e)
+                            .show_alert()
+                            .unwrap();
+                    }
+                }
+            }
+            Message::CancelSchedule => {
+                if let Some(scheduled) = state.scheduled.take() {
+                    schedule::clear();
+                    state.schedule_paused = false;
+                    log::info("已取消定时发送", &[("at", &scheduled.at), ("title", &scheduled.title)]);
+                }
+            }
+            Message::ReloadSchedule => {
+                match schedule::load() {
+                    Ok(scheduled) => {
+                        state.scheduled = scheduled;
+                        state.schedule_error = None;
+                        state.schedule_paused = false;
+                    }
+                    Err(e) => state.schedule_error = Some(e),
+                }
+            }
+            Message::SendScheduled if !state.sending() && state.check_suppression() => {
+                return state.send_scheduled(true);
+            }
+            Message::TestAddress(value) => {
+                state.test_address = value.trim().to_owned();
+            }
+            Message::SendTest => {
+                let task = state.preview
+                    .and_then(|id| state.position(id))
+                    .map(|index| &state.list[index])
+                    .or_else(|| state.list.iter().find(|task| task.status))
+                    .or(state.list.first());
+                let Some(id) = task.map(|task| task.id) else {
+                    return Command::none();
+                };
+                if !state.test_address.is_empty() && !mail::is_valid_email(&state.test_address) {
+                    MessageDialog::new()
+                        .set_type(MessageType::Error)
+                        .set_title("测试发送")
+                        .set_text(&format!("测试收件地址格式不正确: {}", state.test_address))
+                        .show_alert()
+                        .unwrap();
+                    return Command::none();
+                }
+                if !state.check_template() {
+                    return Command::none();
+                }
+                let receiver = state.test_address.clone();
+                if !receiver.is_empty() && !state.confirm_external(&[receiver]) {
+                    return Command::none();
+                }
+                // 测试邮件不算一次发送，不更换批次号，免得正在进行的发送记录对不上日志
+                state.send_message = "发送测试邮件中...".to_owned();
+                return Command::perform(State::send_test(state.clone(), id), Message::EndTest);
+            }
+            Message::EndTest(result) => {
+                match result {
+                    Ok(message) => state.send_message = message,
+                    Err(e) => {
+                        state.send_message = "测试邮件发送失败".to_owned();
+                        MessageDialog::new()
+                            .set_type(MessageType::Error)
+                            .set_title("测试发送")
+                            .set_text(&e)
+                            .show_alert()
+                            .unwrap();
+                    }
+                }
+            }
+            Message::SendOne(id) => {
+                let Some(index) = state.position(id) else {
+                    return Command::none();
+                };
+                // 表格里的按钮只能按行禁用，其它行还在发送时在这里拦下
+                if state.sending() {
+                    state.send_message = "上一批邮件还在发送中，请等待发送结束".to_owned();
+                    return Command::none();
+                }
+                if !state.check_suppression() || !state.check_template() {
+                    return Command::none();
+                }
+                let yes = MessageDialog::new()
+                    .set_type(MessageType::Info)
+                    .set_title("发送确认")
+                    .set_text(&format!("是否确认发送给 {}?", state.list[index].email))
+                    .show_confirm()
+                    .unwrap();
+                if !yes || !state.confirm_external(&[state.list[index].email.clone()]) {
+                    return Command::none();
+                }
+                state.send_message = "发送邮件中...".to_owned();
+                state.list[index].send_status = SendStatus::Sending;
+                state.row_menu = None;
+
+                state.begin_campaign();
+                return Command::perform(State::send_one(state.clone(), id), |(record, tasks)| Message::EndSend(record, tasks));
+            }
+            Message::EndSend(record, tasks) => {
+                let campaign = record.id.clone();
+                if let Err(e) = history::append(*record) {
+                    log::error("保存发送记录失败", &[("campaign", &campaign), ("error", &e)]);
+                    MessageDialog::new()
+                        .set_type(MessageType::Error)
+                        .set_title("发送记录")
+                        .set_text(&format!("本次发送的记录没有保存: {}", e))
+                        .show_alert()
+                        .unwrap();
+                }
+                let mut failed = 0;
+                for result in tasks {
+                    if matches!(result.send_status, SendStatus::Failed(_)) {
+                        failed += 1;
+                    }
+                    if let Some(task) = state.task_mut(result.id) {
+                        // 发送成功的行取消勾选，再次发送时只会发送失败或新勾选的行
+                        if result.send_status == SendStatus::Sent {
+                            task.status = false;
+                        }
+                        task.send_status = result.send_status;
+                        task.attempts = result.attempts;
+                        task.sent_at = result.sent_at;
+                        task.message_id = result.message_id;
+                    }
+                }
+                if failed == 0 {
+                    state.send_message = "发送完毕".to_string();
+                } else {
+                    state.send_message = format!("发送完毕，{}条邮件未发送成功", failed);
+                }
+
+                set_mail_data(&MailData {
+                    remark: state.remark.clone(),
+                    intro: state.intro.clone(),
+                    title: state.title.clone(),
+                    template: state.template_path.clone(),
+                    interval: state.interval,
+                    test_address: state.test_address.clone(),
+                    confirm_threshold: state.confirm_threshold,
+                });
+            }
+            Message::Export => {
+                let file = rfd::FileDialog::new()
+                    .add_filter("csv files (*.csv)", &["csv"])
+                    .set_file_name(format!("发送结果-{}.csv", Local::now().format("%Y%m%d%H%M%S")))
+                    .save_file();
+                if let Some(path) = file {
+                    if let Err(e) = export::write_csv(&path, &state.headers, &state.list) {
+                        MessageDialog::new()
+                            .set_type(MessageType::Error)
+                            .set_title("导出提示")
+                            .set_text(&e)
+                            .show_alert()
+                            .unwrap();
+                    }
+                }
+            }
+            Message::RetryFailed => {
+                if state.sending() || !state.list.iter().any(|task| matches!(task.send_status, SendStatus::Failed(_))) {
+                    return Command::none();
+                }
+                for task in state.list.iter_mut() {
+                    task.status = matches!(task.send_status, SendStatus::Failed(_));
+                }
+                return State::update(state, Message::BeginSend);
+            }
+            Message::PrevPage if state.cur_page > 0 => {
+                state.cur_page -= 1;
+            }
+            Message::NextPage if state.cur_page + 1 < state.page_count() => {
+                state.cur_page += 1;
+            }
+            Message::FirstPage => {
+                state.cur_page = 0;
+            }
+            Message::LastPage => {
+                state.cur_page = state.page_count().saturating_sub(1);
+            }
+            Message::PageInput(value) => {
+                state.page_input = value;
+            }
+            Message::JumpPage => {
+                if let Ok(page) = state.page_input.trim().parse::<usize>() {
+                    state.cur_page = page.clamp(1, state.page_count().max(1)) - 1;
+                }
+                state.page_input.clear();
+            }
+            Message::PageSize(page_size) => {
+                state.page_size = page_size;
+                state.cur_page = 0;
+                state.row_offset = 0.0;
+                return scrollable::snap_to(state.rows.clone(), scrollable::RelativeOffset::START);
+            }
+            Message::ScrollRows(offset) => {
+                state.row_offset = offset.y;
+                return scrollable::scroll_to(state.header.clone(), scrollable::AbsoluteOffset { y: 0.0, ..offset });
+            }
+            Message::Duplicates(action) => {
+                match action {
+                    DuplicateAction::KeepFirst => dedup::keep_first(&mut state.list),
+                    DuplicateAction::KeepAll => state.keep_duplicates = true,
+                    DuplicateAction::Merge => dedup::merge(&mut state.list),
+                }
+                state.mark_duplicates();
+            }
+            Message::AllowedDomains(value) => {
+                state.auth.allowed_domains = value;
+                if let Err(e) = domains::save(&state.auth.username, &state.auth.allowed_domains) {
+                    log::error("保存收件域名失败", &[("user", &state.auth.username), ("error", &e)]);
+                }
+            }
+            Message::ToggleSuppression => {
+                state.show_suppression = !state.show_suppression;
+            }
+            Message::ReloadSuppression => {
+                match suppression::load() {
+                    Ok(entries) => {
+                        state.suppression = entries;
+                        state.suppression_error = None;
+                        state.apply_suppression();
+                    }
+                    Err(e) => state.suppression_error = Some(e),
+                }
+            }
+            Message::ImportSuppression | Message::RemoveSuppression(_) | Message::SuppressRow if !state.check_suppression() => {}
+            Message::ImportSuppression => {
+                let file = rfd::FileDialog::new()
+                    .add_filter("text files (*.txt, *.csv)", &["txt", "csv"])
+                    .pick_file();
+                if let Some(path) = file {
+                    let result = suppression::import(&mut state.suppression, &path)
+                        .and_then(|added| suppression::save(&state.suppression).map(|_| added));
+                    match result {
+                        Ok(added) => {
+                            state.send_message = format!("禁止发送名单新增 {} 个邮箱", added);
+                            state.apply_suppression();
+                        }
+                        Err(e) => {
+                            MessageDialog::new()
+                                .set_type(MessageType::Error)
+                                .set_title("禁止发送名单")
+                                .set_text(&e)
+                                .show_alert()
+                                .unwrap();
+                        }
+                    }
+                }
+            }
+            Message::RemoveSuppression(index) if index < state.suppression.len() => {
+                state.suppression.remove(index);
+                if let Err(e) = suppression::save(&state.suppression) {
+                    log::error("保存禁止发送名单失败", &[("error", &e)]);
+                }
+                state.apply_suppression();
+            }
+            Message::RowMenu(id) => {
+                state.row_menu = Some(id);
+                state.suppress_reason.clear();
+            }
+            Message::CloseRowMenu => {
+                state.row_menu = None;
+            }
+            Message::SuppressReason(value) => {
+                state.suppress_reason = value;
+            }
+            Message::SuppressRow => {
+                let Some(index) = state.row_menu.take().and_then(|id| state.position(id)) else {
+                    return Command::none();
+                };
+                let email = state.list[index].email.clone();
+                suppression::add(&mut state.suppression, &email, &state.suppress_reason);
+                if let Err(e) = suppression::save(&state.suppression) {
+                    MessageDialog::new()
+                        .set_type(MessageType::Error)
+                        .set_title("禁止发送名单")
+                        .set_text(&e)
+                        .show_alert()
+                        .unwrap();
+                }
+                log::info("加入禁止发送名单", &[("recipient", &email), ("reason", &state.suppress_reason)]);
+                state.apply_suppression();
+            }
+            Message::ToggleLog => {
+                state.show_log = !state.show_log;
+                if state.show_log {
+                    return State::update(state, Message::Tick);
+                }
+            }
+            Message::LogLevel(level) => {
+                state.log_level = level;
+            }
+            Message::OpenLogDir => {
+                if let Err(e) = open_path(&log::dir()) {
+                    MessageDialog::new()
+                        .set_type(MessageType::Error)
+                        .set_title("日志提示")
+                        .set_text(&e)
+                        .show_alert()
+                        .unwrap();
+                }
+            }
+            Message::Tick => {
+                let mut commands = vec![];
+                if state.show_log {
+                    let entries = log::session();
+                    let changed = entries.len() != state.log_entries.len()
+                        || entries.last().map(|entry| &entry.time) != state.log_entries.last().map(|entry| &entry.time);
+                    state.log_entries = entries;
+                    if changed {
+                        commands.push(scrollable::snap_to(state.log_scroll.clone(), scrollable::RelativeOffset::END));
+                    }
+                }
+                // 上一批还在发送时等它结束再开始
+                // 禁止发送名单读取失败时先不发，等修复后重新读取
+                if !state.sending() && state.suppression_error.is_none() && !state.schedule_paused && state.scheduled.as_ref().is_some_and(|scheduled| scheduled.remaining().is_none()) {
+                    commands.push(state.send_scheduled(false));
+                }
+                return Command::batch(commands);
+            }
+            _ => {}
+        }
+
+        Command::none()
+    }
+
+    async fn send(state: State) -> (Box<history::Campaign>, Vec<Tasks>) {
+        let selected = state.list.iter().filter(|task| task.status).cloned().collect();
+        let state = Arc::new(state);
+        let sender = state.clone();
+        let tasks = blocking(move || sender.send_all()).await.unwrap_or_else(|e| failed(selected, &e));
+        (Box::new(state.campaign_record(&tasks)), tasks)
+    }
+
+    async fn send_one(state: State, id: usize) -> (Box<history::Campaign>, Vec<Tasks>) {
+        let selected = state.list.iter().filter(|task| task.id == id).cloned().collect();
+        let state = Arc::new(state);
+        let sender = state.clone();
+        let tasks = blocking(move || sender.send_row(id)).await.unwrap_or_else(|e| failed(selected, &e));
+        (Box::new(state.campaign_record(&tasks)), tasks)
+    }
+
+    async fn send_test(state: State, id: usize) -> Result<String, String> {
+        blocking(move || state.test_row(id)).await?
+    }
+
+    fn send_all(&self) -> Vec<Tasks> {
+        let creds = Credentials::new(self.auth.username.clone(), self.auth.password.clone());
+        let selected = self.list.iter().filter(|task| task.status).count().to_string();
+        log::info("开始批量发送", &[("campaign", &self.campaign), ("sender", &self.sender()), ("title", &self.title), ("count", &selected)]);
+        let mut tasks = vec![];
+        for task in &self.list {
+            if task.status {
+                tasks.push(self.send_task(task, &creds));
+            } else if task.send_status == SendStatus::Pending {
+                let mut task = task.clone();
+                task.send_status = SendStatus::Skipped("未勾选".to_owned());
+                tasks.push(task);
+            }
+        }
+        log::info("批量发送结束", &[("campaign", &self.campaign)]);
+
+        tasks
+    }
+
+    fn send_row(&self, id: usize) -> Vec<Tasks> {
+        let creds = Credentials::new(self.auth.username.clone(), self.auth.password.clone());
+        self.list.iter()
+            .filter(|task| task.id == id)
+            .map(|task| self.send_task(task, &creds))
+            .collect()
+    }
+
+    // 按正式发送的方式渲染，但收件人换成测试地址（默认发给自己），主题加上 [TEST] 前缀
+    fn test_row(&self, id: usize) -> Result<String, String> {
+        let creds = Credentials::new(self.auth.username.clone(), self.auth.password.clone());
+        let Some(task) = self.list.iter().find(|task| task.id == id) else {
+            return Err("找不到要测试的数据行".to_owned());
+        };
+        let receiver = if self.test_address.is_empty() {
+            self.sender()
+        } else {
+            self.test_address.clone()
+        };
+        let (subject, html) = self.render(task);
+        mail::throttle(Duration::from_millis(self.interval));
+        match send_mail(&format!("[TEST]{}", subject), &html, &self.sender(), &receiver, creds) {
+            Ok(receipt) => {
+                log::info("测试邮件发送成功", &[("recipient", &receiver), ("row", &task.email),
+                    ("message_id", &receipt.message_id), ("smtp", &receipt.response)]);
+                Ok(format!("测试邮件已发送至 {}", receiver))
+            }
+            Err(e) => {
+                log::error("测试邮件发送失败", &[("recipient", &receiver), ("row", &task.email), ("error", &e)]);
+                Err(e)
+            }
+        }
+    }
+
+    fn send_task(&self, task: &Tasks, creds: &Credentials) -> Tasks {
+        let mut task = task.clone();
+        if let Some(reason) = suppression::find(&self.suppression, &task.email) {
+            log::warn("收件人在禁止发送名单中，已跳过", &[("campaign", &self.campaign), ("recipient", &task.email), ("reason", reason)]);
+            task.send_status = SendStatus::Skipped(format!("{}: {}", SUPPRESSED, reason));
+            return task;
+        }
+        if !self.allow_external && !mail::domain_allowed(&task.email, &self.auth.domains()) {
+            log::warn("收件人不在允许的域名内，已跳过", &[("campaign", &self.campaign), ("recipient", &task.email)]);
+            task.send_status = SendStatus::Skipped("收件域名不在允许范围内".to_owned());
+            return task;
+        }
+        log::debug("开始生成邮件 html", &[("campaign", &self.campaign), ("recipient", &task.email), ("name", &task.name)]);
+        let (subject, html) = self.render(&task);
+        log::debug("生成邮件 html 成功", &[("campaign", &self.campaign), ("recipient", &task.email), ("name", &task.name)]);
+        mail::throttle(Duration::from_millis(self.interval));
+        task.attempts += 1;
+        task.sent_at = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        match send_mail(&subject, &html, &self.sender(),
+                        &task.email, creds.clone()) {
+            Ok(receipt) => {
+                task.send_status = SendStatus::Sent;
+                task.message_id = receipt.message_id.clone();
+                log::info("邮件发送成功", &[("campaign", &self.campaign), ("recipient", &task.email), ("name", &task.name),
+                    ("subject", &subject), ("message_id", &receipt.message_id), ("smtp", &receipt.response)]);
+            }
+            Err(e) => {
+                log::error("邮件发送失败", &[("campaign", &self.campaign), ("recipient", &task.email), ("name", &task.name),
+                    ("subject", &subject), ("error", &e)]);
+                task.send_status = SendStatus::Failed(e);
             }
         }
+        task
+    }
+
+    fn begin_campaign(&mut self) {
+        self.campaign = campaign_id();
+        self.started_at = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    }
 
-        failed_task
+    fn campaign_record(&self, tasks: &[Tasks]) -> history::Campaign {
+        let mut headers: Vec<&Header> = self.headers.iter().skip(FIXED_COLUMNS).collect();
+        headers.sort_by_key(|header| header.index);
+        history::Campaign {
+            id: self.campaign.clone(),
+            title: self.title.clone(),
+            intro: self.intro.clone(),
+            remark: self.remark.clone(),
+            template: self.template_path.clone(),
+            source: self.source.clone(),
+            source_hash: self.source_hash.clone(),
+            sender: self.sender(),
+            started_at: self.started_at.clone(),
+            ended_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            headers: headers.iter().map(|header| header.name.clone()).collect(),
+            recipients: tasks.iter()
+                .map(|task| history::Recipient {
+                    email: task.email.clone(),
+                    name: task.name.clone(),
+                    status: task.send_status.clone(),
+                    message_id: task.message_id.clone(),
+                    sent_at: task.sent_at.clone(),
+                    values: headers.iter().map(|header| task.value(header.index)).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// 用发送记录中的数据、主题和提示信息替换当前列表，只勾选上次发送失败的行
+    fn reopen(&mut self, campaign: &history::Campaign) {
+        let mut headers = excel::build_headers(campaign.headers.clone());
+        layout::apply(&mut headers);
+        self.headers = headers;
+        self.list = campaign.recipients.iter()
+            .enumerate()
+            .map(|(id, recipient)| {
+                let mut task = Tasks { id, ..Tasks::default() };
+                for (index, value) in recipient.values.iter().enumerate() {
+                    task.set_value(index, value.clone());
+                }
+                task.send_status = recipient.status.clone();
+                task.status = matches!(task.send_status, SendStatus::Failed(_));
+                task.attempts = if recipient.sent_at.is_empty() { 0 } else { 1 };
+                task.sent_at = recipient.sent_at.clone();
+                task.message_id = recipient.message_id.clone();
+                task
+            })
+            .collect();
+        self.title = campaign.title.clone();
+        self.intro = campaign.intro.clone();
+        self.remark = campaign.remark.clone();
+        match html::load_template(&campaign.template) {
+            Ok(template) => {
+                self.template_path = campaign.template.clone();
+                self.template = template;
+            }
+            Err(e) => {
+                log::warn("加载模板失败，改用默认模板", &[("campaign", &campaign.id), ("error", &e)]);
+                self.template_path = String::new();
+                self.template = html::DEFAULT_TEMPLATE.to_owned();
+            }
+        }
+        self.source = campaign.source.clone();
+        self.source_hash = campaign.source_hash.clone();
+        self.search_value = String::new();
+        self.status_filter = StatusFilter::All;
+        self.cur_page = 0;
+        self.preview = None;
+        self.keep_duplicates = false;
+        self.row_menu = None;
+        self.mark_duplicates();
+        self.apply_suppression();
+        self.send_message = format!("已重新打开 {} 的发送记录", campaign.started_at);
+    }
+
+    /// 名单中的行取消勾选并标记为已跳过，移出名单的行恢复为未发送
+    fn apply_suppression(&mut self) {
+        for task in self.list.iter_mut().filter(|task| task.send_status != SendStatus::Sent) {
+            match suppression::find(&self.suppression, &task.email) {
+                Some(reason) => {
+                    task.status = false;
+                    task.send_status = SendStatus::Skipped(format!("{}: {}", SUPPRESSED, reason));
+                }
+                None => {
+                    if task.send_status.error().starts_with(SUPPRESSED) {
+                        task.send_status = SendStatus::Pending;
+                    }
+                }
+            }
+        }
+    }
+
+    /// 设置了允许的域名时，列出范围外的所有地址请用户确认，确认后本次发送不再拦截
+    fn confirm_external(&mut self, emails: &[String]) -> bool {
+        let domains = self.auth.domains();
+        let external: Vec<&String> = emails.iter().filter(|email| !mail::domain_allowed(email, &domains)).collect();
+        self.allow_external = false;
+        if external.is_empty() {
+            return true;
+        }
+        let list = external.iter().map(|email| email.as_str()).collect::<Vec<_>>().join("\n");
+        let yes = MessageDialog::new()
+            .set_type(MessageType::Warning)
+            .set_title("域名确认")
+            .set_text(&format!("以下 {} 个收件人不在允许的域名（{}）内:\n{}\n\n是否仍然发送给这些地址?",
+                               external.len(), domains.join("、"), list))
+            .show_confirm()
+            .unwrap();
+        if yes {
+            log::warn("用户确认发送到允许域名以外的地址", &[("sender", &self.sender()), ("count", &external.len().to_string()), ("recipients", &list.replace('\n', ","))]);
+            self.allow_external = true;
+        }
+        yes
+    }
+
+    fn send_summary(&self) -> String {
+        let selected: Vec<&Tasks> = self.list.iter().filter(|task| task.status).collect();
+        let suppressed = selected.iter().filter(|task| suppression::find(&self.suppression, &task.email).is_some()).count();
+        let invalid = selected.iter().filter(|task| !mail::is_valid_email(task.email.trim())).count();
+        let duplicate = selected.iter().filter(|task| task.duplicate).count();
+        let merged: usize = selected.iter().map(|task| task.merged.len()).sum();
+        let subject = selected.first().map(|task| self.subject(task)).unwrap_or_default();
+        // 按发送间隔估算，不含服务器响应时间
+        let millis = selected.len().saturating_sub(1) as u64 * self.interval;
+
+        let mut lines = vec![
+            format!("发件人: {}", self.sender()),
+            format!("已勾选: {} 个收件人（其中 {} 个合并了 {} 行重复数据）", selected.len(),
+                    selected.iter().filter(|task| !task.merged.is_empty()).count(), merged),
+            format!("未勾选: {} 行", self.list.len() - selected.len()),
+            format!("在禁止发送名单中（将跳过）: {} 个", suppressed),
+            format!("邮箱格式有误: {} 个", invalid),
+            format!("邮箱重复: {} 个", duplicate),
+            format!("第一封邮件主题: {}", subject),
+            format!("预计耗时: 至少 {}（发送间隔 {} 毫秒）", format_duration(millis / 1000), self.interval),
+        ];
+        if invalid > 0 || duplicate > 0 {
+            lines.push("请注意：格式有误的邮箱会发送失败，重复的邮箱会收到多封邮件".to_owned());
+        }
+        lines.join("\n")
+    }
+
+    /// 发送前的检查和确认，全部通过时返回 true
+    fn confirm_send(&mut self, question: &str) -> bool {
+        if self.list.is_empty() || !self.check_suppression() || !self.check_template() {
+            return false;
+        }
+        let count = self.list.iter().filter(|task| task.status).count();
+        if count == 0 {
+            MessageDialog::new()
+                .set_type(MessageType::Info)
+                .set_title("发送确认")
+                .set_text("没有勾选要发送的数据")
+                .show_alert()
+                .unwrap();
+            return false;
+        }
+        let yes = MessageDialog::new()
+            .set_type(MessageType::Info)
+            .set_title("发送确认")
+            .set_text(&format!("{}\n\n{}", self.send_summary(), question))
+            .show_confirm()
+            .unwrap();
+        if !yes {
+            return false;
+        }
+        if self.confirm_threshold > 0 && count > self.confirm_threshold {
+            let yes = MessageDialog::new()
+                .set_type(MessageType::Warning)
+                .set_title("发送确认")
+                .set_text(&format!("本次将发送 {} 封邮件，超过了 {} 封，请再次确认是否发送?", count, self.confirm_threshold))
+                .show_confirm()
+                .unwrap();
+            if !yes {
+                return false;
+            }
+        }
+        let selected: Vec<String> = self.list.iter().filter(|task| task.status).map(|task| task.email.clone()).collect();
+        self.confirm_external(&selected)
+    }
+
+    fn ticks(&self) -> bool {
+        self.show_log || self.scheduled.is_some()
+    }
+
+    /// 有邮件正在发送时不能再开始新的发送，否则同一行会被重复发送
+    fn sending(&self) -> bool {
+        self.list.iter().any(|task| task.send_status == SendStatus::Sending)
+    }
+
+    fn start_send(&mut self) -> Command<Message> {
+        self.send_message = "发送邮件中...".to_owned();
+        for task in self.list.iter_mut().filter(|task| task.status) {
+            task.send_status = SendStatus::Sending;
+        }
+
+        self.begin_campaign();
+        // 定时发送由 Tick 触发，不在 changes_list 中，这里自己刷新
+        self.refresh();
+        Command::perform(State::send(self.clone()), |(record, tasks)| Message::EndSend(record, tasks))
+    }
+
+    /// 保存当前勾选的行和邮件内容，供定时发送使用
+    fn schedule(&self, at: String) -> schedule::Scheduled {
+        let count = self.headers.len() - FIXED_COLUMNS;
+        let values = |task: &Tasks| (0..count).map(|index| task.value(index)).collect::<Vec<_>>();
+        schedule::Scheduled {
+            at,
+            title: self.title.clone(),
+            intro: self.intro.clone(),
+            remark: self.remark.clone(),
+            template_path: self.template_path.clone(),
+            template: self.template.clone(),
+            interval: self.interval,
+            allow_external: self.allow_external,
+            source: self.source.clone(),
+            source_hash: self.source_hash.clone(),
+            columns: self.headers.iter()
+                .skip(FIXED_COLUMNS)
+                .map(|header| schedule::Column {
+                    name: header.name.clone(),
+                    index: header.index,
+                    width: header.width,
+                    check: header.check,
+                    raw: header.raw,
+                })
+                .collect(),
+            rows: self.list.iter()
+                .filter(|task| task.status)
+                .map(|task| schedule::Row {
+                    values: values(task),
+                    merged: task.merged.iter().map(values).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// 到时间后把定时发送的数据载入表格并开始发送。发送时间无效、程序关闭期间错过了，
+    /// 或者会替换掉界面上的其它数据时，先询问是否发送；不发送则暂停，等用户点击"现在发送"或改期
+    fn send_scheduled(&mut self, confirmed: bool) -> Command<Message> {
+        let Some(scheduled) = self.scheduled.as_ref() else {
+            return Command::none();
+        };
+        if !confirmed {
+            let mut reasons = vec![];
+            match scheduled.due_at() {
+                None => reasons.push(format!("定时发送时间「{}」无效", scheduled.at)),
+                Some(at) if Local::now() - at > chrono::Duration::minutes(10) => {
+                    reasons.push(format!("原定于 {} 发送，已经错过发送时间", scheduled.at));
+                }
+                Some(_) => {}
+            }
+            // 还是安排定时发送时的那批数据，或者表格是空的，就不用再问
+            if !self.list.is_empty() && !self.schedule(scheduled.at.clone()).same_content(scheduled) {
+                reasons.push("发送前会用定时保存的数据替换当前的列表、主题、模板、引言和提示信息".to_owned());
+            }
+            if !reasons.is_empty() {
+                let yes = MessageDialog::new()
+                    .set_type(MessageType::Warning)
+                    .set_title("定时发送")
+                    .set_text(&format!("「{}」{}，是否现在发送?", scheduled.title, reasons.join("；")))
+                    .show_confirm()
+                    .unwrap();
+                if !yes {
+                    log::warn("定时发送未确认，已暂停", &[("at", &scheduled.at), ("title", &scheduled.title), ("reason", &reasons.join("；"))]);
+                    self.schedule_paused = true;
+                    return Command::none();
+                }
+            }
+        }
+        let Some(scheduled) = self.scheduled.take() else {
+            return Command::none();
+        };
+        schedule::clear();
+        self.schedule_paused = false;
+
+        let task = |id: usize, values: &[String]| {
+            let mut task = Tasks { id, status: true, ..Tasks::default() };
+            for (index, value) in values.iter().enumerate() {
+                task.set_value(index, value.clone());
+            }
+            task
+        };
+        let mut headers = excel::build_headers(vec![]);
+        headers.extend(scheduled.columns.iter().map(|column| Header {
+            name: column.name.clone(),
+            index: column.index,
+            width: column.width,
+            check: column.check,
+            raw: column.raw,
+            ..Header::default()
+        }));
+        self.headers = headers;
+        self.list = scheduled.rows.iter()
+            .enumerate()
+            .map(|(id, row)| Tasks {
+                merged: row.merged.iter().map(|values| task(id, values)).collect(),
+                ..task(id, &row.values)
+            })
+            .collect();
+        self.title = scheduled.title;
+        self.intro = scheduled.intro;
+        self.remark = scheduled.remark;
+        self.template_path = scheduled.template_path;
+        self.template = scheduled.template;
+        self.interval = scheduled.interval;
+        self.source = scheduled.source;
+        self.source_hash = scheduled.source_hash;
+        self.search_value = String::new();
+        self.status_filter = StatusFilter::All;
+        self.cur_page = 0;
+        self.preview = None;
+        self.row_menu = None;
+        self.keep_duplicates = true;
+        self.mark_duplicates();
+        self.apply_suppression();
+
+        log::info("开始定时发送", &[("at", &scheduled.at), ("title", &self.title), ("count", &self.list.len().to_string())]);
+        self.allow_external = scheduled.allow_external;
+        self.start_send()
+    }
+
+    fn check_suppression(&self) -> bool {
+        if let Some(e) = &self.suppression_error {
+            MessageDialog::new()
+                .set_type(MessageType::Error)
+                .set_title("禁止发送名单")
+                .set_text(&format!("{}\n\n请修复或删除该文件后点击\"重新读取\"", e))
+                .show_alert()
+                .unwrap();
+            return false;
+        }
+        true
+    }
+
+    fn check_template(&self) -> bool {
+        let missing = self.missing_columns();
+        if !missing.is_empty() {
+            MessageDialog::new()
+                .set_type(MessageType::Error)
+                .set_title("发送确认")
+                .set_text(&format!("模板引用了不存在的列: {}", missing.join("、")))
+                .show_alert()
+                .unwrap();
+            return false;
+        }
+        true
+    }
+
+    fn position(&self, id: usize) -> Option<usize> {
+        self.list.iter().position(|task| task.id == id)
+    }
+
+    fn task_mut(&mut self, id: usize) -> Option<&mut Tasks> {
+        self.list.iter_mut().find(|task| task.id == id)
+    }
+
+    fn page_rows(&self) -> usize {
+        match self.page_size {
+            PageSize::Rows(rows) => rows,
+            PageSize::All => self.filtered.len().max(1),
+        }
+    }
+
+    fn page_count(&self) -> usize {
+        self.filtered.len().div_ceil(self.page_rows())
+    }
+
+    /// 列表中的邮箱有变化（导入、编辑、去重）后重新标记重复的行
+    fn mark_duplicates(&mut self) {
+        self.duplicates = dedup::mark(&mut self.list);
+    }
+
+    fn refresh(&mut self) {
+        if self.preview.is_some_and(|id| self.position(id).is_none()) {
+            self.preview = None;
+        }
+        self.filtered = (0..self.list.len())
+            .filter(|index| {
+                let task = &self.list[*index];
+                self.status_filter.matches(&task.send_status) && search::matches(task, &self.headers, &self.search_value)
+            })
+            .collect();
+        let pages = self.page_count();
+        if self.cur_page >= pages {
+            self.cur_page = pages.saturating_sub(1);
+        }
+        // 全部显示时直接按 filtered 取可见的行，不复制整个列表
+        if self.page_size == PageSize::All {
+            self.page.clear();
+            return;
+        }
+        let start = self.cur_page * self.page_rows();
+        let end = (start + self.page_rows()).min(self.filtered.len());
+        self.page = self.filtered[start..end].iter()
+            .map(|index| self.list[*index].clone())
+            .collect();
+    }
+
+    fn render(&self, task: &Tasks) -> (String, String) {
+        let html = generate_html(task, &self.headers, &self.template,
+                                 &self.intro, &self.remark, &self.sender());
+        (self.subject(task), html)
+    }
+
+    // 主题中没有占位符时沿用原来的 "[姓名]主题" 格式
+    fn subject(&self, task: &Tasks) -> String {
+        if template::placeholders(&self.title).is_empty() {
+            format!("[{}]{}", task.name, self.title)
+        } else {
+            template::render(&self.title, task, &self.headers)
+        }
+    }
+
+    fn sender(&self) -> String {
+        format!("{}@wondersgroup.com", self.auth.username)
+    }
+
+    fn missing_columns(&self) -> Vec<String> {
+        if self.headers.is_empty() {
+            return vec![];
+        }
+        let mut missing = vec![];
+        for text in [&self.title, &self.intro, &self.remark] {
+            for name in template::missing_columns(text, &self.headers) {
+                if !missing.contains(&name) {
+                    missing.push(name);
+                }
+            }
+        }
+        for name in html::template_columns(&self.template) {
+            if !missing.contains(&name) && !template::has_column(&self.headers, &name) {
+                missing.push(name);
+            }
+        }
+        missing
     }
 }
 
-impl Tasks {
-    fn view(&self) -> Element<'_, Message> {
-        let checkbox = checkbox("", true)
-            // .on_toggle(TaskMessage::Completed)
-            .size(17)
-            .text_shaping(text::Shaping::Advanced);
-        let email = text(&self.email);
-        let row = row![
-                    checkbox,
-            email,
-            text(&self.seq),
-            text(&self.name),
-                    button("发送")
-                        // .on_press()
-                        .padding(10)
-            .style(theme::Button::Text),
-                ]
-            .spacing(20)
-            .into();
-        row
+impl SendStatus {
+    fn label(&self) -> &str {
+        match self {
+            SendStatus::Pending => "未发送",
+            SendStatus::Sending => "发送中",
+            SendStatus::Sent => "发送成功",
+            SendStatus::Failed(_) => "发送失败",
+            SendStatus::Skipped(_) => "已跳过",
+        }
+    }
+
+    fn error(&self) -> &str {
+        match self {
+            SendStatus::Failed(reason) | SendStatus::Skipped(reason) => reason,
+            _ => "",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            SendStatus::Sent => Color::from([0.1, 0.6, 0.2]),
+            SendStatus::Failed(_) => Color::from([0.8, 0.2, 0.2]),
+            SendStatus::Sending => Color::from([0.2, 0.4, 0.8]),
+            _ => Color::from([0.5, 0.5, 0.5]),
+        }
+    }
+}
+
+impl PageSize {
+    const OPTIONS: [PageSize; 5] = [
+        PageSize::Rows(20),
+        PageSize::Rows(50),
+        PageSize::Rows(100),
+        PageSize::Rows(200),
+        PageSize::All,
+    ];
+}
+
+impl std::fmt::Display for PageSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PageSize::Rows(rows) => write!(f, "每页 {} 条", rows),
+            PageSize::All => write!(f, "全部显示"),
+        }
+    }
+}
+
+impl StatusFilter {
+    const ALL: [StatusFilter; 6] = [
+        StatusFilter::All,
+        StatusFilter::Pending,
+        StatusFilter::Sending,
+        StatusFilter::Sent,
+        StatusFilter::Failed,
+        StatusFilter::Skipped,
+    ];
+
+    fn matches(&self, status: &SendStatus) -> bool {
+        match self {
+            StatusFilter::All => true,
+            StatusFilter::Pending => *status == SendStatus::Pending,
+            StatusFilter::Sending => *status == SendStatus::Sending,
+            StatusFilter::Sent => *status == SendStatus::Sent,
+            StatusFilter::Failed => matches!(status, SendStatus::Failed(_)),
+            StatusFilter::Skipped => matches!(status, SendStatus::Skipped(_)),
+        }
+    }
+}
+
+impl std::fmt::Display for StatusFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            StatusFilter::All => "全部状态",
+            StatusFilter::Pending => "只看未发送",
+            StatusFilter::Sending => "只看发送中",
+            StatusFilter::Sent => "只看发送成功",
+            StatusFilter::Failed => "只看发送失败",
+            StatusFilter::Skipped => "只看已跳过",
+        };
+        write!(f, "{}", label)
     }
+}
 
+impl Tasks {
     fn at(&self, index: usize) -> String {
         self.info.get(index).map(|s| s.to_string()).unwrap_or_default()
     }
-}
-fn get_info(p0: &Tasks, msg: &str, type_str: &str) -> String {
-    let current_time = Local::now();
-    let formatted_time = current_time.format("%Y-%m-%d %H:%M:%S").to_string();
-    format!("{}-{}: {} {}\n", formatted_time, type_str, p0.name, msg)
+
+    fn value(&self, index: usize) -> String {
+        match index {
+            0 => self.email.clone(),
+            1 => self.seq.to_string(),
+            2 => self.name.clone(),
+            _ => self.at(index - 3),
+        }
+    }
+
+    fn set_value(&mut self, index: usize, value: String) {
+        match index {
+            0 => self.email = value,
+            1 => self.seq = value.parse().unwrap_or(self.seq),
+            2 => self.name = value,
+            _ => {
+                if self.info.len() <= index - 3 {
+                    self.info.resize(index - 2, String::new());
+                }
+                self.info[index - 3] = value;
+            }
+        }
+    }
 }
 
 impl<'a> table::Column<'a, Message, Theme, Renderer> for Header {
@@ -495,22 +2241,64 @@ impl<'a> table::Column<'a, Message, Theme, Renderer> for Header {
     fn header(&'a self, col_index: usize) -> Element<'a, Message> {
         if col_index == 0 {
             container(checkbox("", self.check).on_toggle(Message::AllSelect)).height(24).center_y().into()
-        } else {
+        } else if col_index == 1 {
             container(text(&self.name)).height(24).center_y().into()
+        } else {
+            let raw = checkbox("HTML", self.raw)
+                .on_toggle(move |raw| Message::RawHtml(col_index, raw))
+                .size(12)
+                .text_size(12);
+            let show = checkbox("", self.check)
+                .on_toggle(move |show| Message::ShowColumn(col_index, show));
+            let label = match self.sort {
+                Some(true) => format!("{} ↑", self.name),
+                Some(false) => format!("{} ↓", self.name),
+                None => self.name.clone(),
+            };
+            let sort = mouse_area(text(label))
+                .on_press(Message::DragColumn(col_index))
+                .on_release(Message::DropColumn(col_index));
+            container(row![show, sort, raw].spacing(5)).height(24).center_y().into()
         }
     }
 
-    fn cell(&'a self, col_index: usize, row_index: usize, row: &'a Self::Row) -> Element<'a, Message> {
+    fn cell(&'a self, col_index: usize, _row_index: usize, row: &'a Self::Row) -> Element<'a, Message> {
         let content: Element<_> = if col_index == 0 {
-            checkbox("", row.status).on_toggle(move |enable| Message::Enable(row_index, enable)).into()
+            checkbox("", row.status).on_toggle(move |enable| Message::Enable(row.id, enable)).into()
         } else if col_index == 1 {
-            text(&row.email).into()
-        } else if col_index == 2 {
-            text(&row.seq).into()
-        } else if col_index == 3 {
-            text(&row.name).into()
+            let label = match row.send_status.error() {
+                "" => row.send_status.label().to_owned(),
+                reason => format!("{}: {}", row.send_status.label(), reason),
+            };
+            let send = button(text("发送").size(14))
+                .on_press_maybe((row.send_status != SendStatus::Sending).then_some(Message::SendOne(row.id)))
+                .padding([2, 6])
+                .style(theme::Button::Text);
+            row![send, text(label).style(row.send_status.color())].spacing(5).align_items(alignment::Alignment::Center).into()
+        } else if let Some((_, value)) = self.editing.as_ref().filter(|(id, _)| *id == row.id) {
+            text_input("", value)
+                .id(Id::new(EDIT_CELL))
+                .on_input(Message::EditCell)
+                .on_submit(Message::CommitEdit)
+                .padding(2)
+                .into()
         } else {
-            text(&row.at(col_index - 4)).into()
+            // 手动修改过的单元格用橙色标出，重复的邮箱用紫色标出
+            let mut value = row.value(self.index);
+            if self.index == 0 && !row.merged.is_empty() {
+                value = format!("{} (合并 {} 行)", value, row.merged.len() + 1);
+            }
+            let value = if row.edited.contains(&self.index) {
+                text(value).style(Color::from([0.9, 0.5, 0.0]))
+            } else if self.index == 0 && row.duplicate {
+                text(value).style(Color::from([0.6, 0.2, 0.7]))
+            } else {
+                text(value)
+            };
+            mouse_area(container(value).width(Length::Fill))
+                .on_press(Message::ClickCell(row.id, col_index))
+                .on_right_press(Message::RowMenu(row.id))
+                .into()
         };
         container(content)
             .width(Length::Fill)
@@ -524,7 +2312,7 @@ impl<'a> table::Column<'a, Message, Theme, Renderer> for Header {
     }
 
     fn resize_offset(&self) -> Option<f32> {
-        None
+        self.resize
     }
 }
 
@@ -537,4 +2325,27 @@ fn read_mail_data() -> MailData {
 
 fn set_mail_data(data: &MailData) {
     fs::write(MAIL_FILE, serde_json::to_string(data).unwrap()).ok();
-}
\ No newline at end of file
+}
+
+fn format_duration(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{} 秒", seconds),
+        60..=3599 => format!("{} 分 {} 秒", seconds / 60, seconds % 60),
+        _ => format!("{} 小时 {} 分", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+fn campaign_id() -> String {
+    Local::now().format("%Y%m%d%H%M%S%3f").to_string()
+}
+
+pub fn open_path(path: &Path) -> Result<(), String> {
+    let result = if cfg!(target_os = "windows") {
+        process::Command::new("cmd").arg("/C").arg("start").arg("").arg(path).spawn()
+    } else if cfg!(target_os = "macos") {
+        process::Command::new("open").arg(path).spawn()
+    } else {
+        process::Command::new("xdg-open").arg(path).spawn()
+    };
+    result.map(|_| ()).map_err(|e| format!("打开 {} 失败: {}", path.display(), e))
+}
@@ -0,0 +1,62 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use iced::widget::{button, column, container, horizontal_space, row, scrollable, text};
+use iced::{theme, Element, Length};
+use crate::{template, Message, State, FIXED_COLUMNS};
+
+pub fn view(state: &State, index: usize) -> Element<'_, Message> {
+    let task = &state.list[index];
+    let subject = state.subject(task);
+
+    let mut table = column![].spacing(6);
+    for header in state.headers.iter().skip(FIXED_COLUMNS) {
+        if !header.check {
+            continue;
+        }
+        table = table.push(row![
+            text(&header.name).width(120),
+            text(task.value(header.index)).width(Length::Fill),
+        ].spacing(10));
+    }
+
+    let intro = template::render(&state.intro, task, &state.headers);
+    let remark = template::render(&state.remark, task, &state.headers);
+    let mut body = column![text(format!("收件人: {}", task.email)), text(format!("主题: {}", subject)).size(18)]
+        .spacing(10);
+    if !intro.is_empty() {
+        body = body.push(text(intro));
+    }
+    body = body.push(table);
+    if !task.merged.is_empty() {
+        body = body.push(text(format!("该邮件合并了 {} 行数据，完整内容请在浏览器中查看", task.merged.len() + 1)));
+    }
+    if !remark.is_empty() {
+        body = body.push(text(format!("*附：{}", remark)));
+    }
+
+    let nav = row![
+        button("上一位").padding([5, 10]).on_press(Message::PrevPreview).style(theme::Button::Secondary),
+        button("下一位").padding([5, 10]).on_press(Message::NextPreview).style(theme::Button::Secondary),
+        text(format!("{}/{}", index + 1, state.list.len())),
+        horizontal_space(),
+        button("在浏览器中打开").padding([5, 10]).on_press(Message::OpenPreview).style(theme::Button::Secondary),
+        button("关闭").padding([5, 10]).on_press(Message::ClosePreview).style(theme::Button::Secondary),
+    ].spacing(10);
+
+    container(column![nav, scrollable(body)].spacing(10))
+        .width(480)
+        .height(Length::Fill)
+        .padding(10)
+        .style(theme::Container::Box)
+        .into()
+}
+
+/// 将预览内容写入临时 html 文件并用系统默认浏览器打开
+pub fn open_in_browser(subject: &str, html: &str) -> Result<(), String> {
+    let path: PathBuf = env::temp_dir().join("kt-mail-preview.html");
+    let page = format!(r#"<!DOCTYPE html><html><head><meta charset="utf-8"><title>{}</title></head><body>{}</body></html>"#,
+                       crate::html::escape(subject), html);
+    fs::write(&path, page).map_err(|e| format!("写入预览文件失败: {}", e))?;
+    crate::open_path(&path)
+}
@@ -0,0 +1,141 @@
+use std::fs;
+use std::io::ErrorKind;
+use chrono::{DateTime, Duration, Local, NaiveDateTime, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use crate::SCHEDULE_FILE;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// 等待定时发送的邮件，保存发送时所需的全部数据，程序重启后仍然有效
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scheduled {
+    pub at: String,
+    pub title: String,
+    pub intro: String,
+    pub remark: String,
+    pub template_path: String,
+    pub template: String,
+    pub interval: u64,
+    pub allow_external: bool,
+    pub source: String,
+    pub source_hash: String,
+    /// 按界面上的顺序保存的数据列
+    pub columns: Vec<Column>,
+    /// 勾选的行，每行的值按列的导入顺序保存
+    pub rows: Vec<Row>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Column {
+    pub name: String,
+    pub index: usize,
+    pub width: f32,
+    pub check: bool,
+    pub raw: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Row {
+    pub values: Vec<String>,
+    pub merged: Vec<Vec<String>>,
+}
+
+impl Scheduled {
+    pub fn due_at(&self) -> Option<DateTime<Local>> {
+        NaiveDateTime::parse_from_str(&self.at, TIME_FORMAT)
+            .ok()
+            .and_then(|at| Local.from_local_datetime(&at).earliest())
+    }
+
+    /// 距发送还剩的时间，已到时间或时间无效时返回 `None`，两者用 [`Scheduled::due_at`] 区分
+    pub fn remaining(&self) -> Option<Duration> {
+        self.due_at()
+            .map(|at| at - Local::now())
+            .filter(|remaining| *remaining > Duration::zero())
+    }
+
+    /// 邮件内容和要发送的数据是否相同，不比较发送时间、列宽等设置
+    pub fn same_content(&self, other: &Scheduled) -> bool {
+        let columns = |scheduled: &Scheduled| scheduled.columns.iter()
+            .map(|column| (column.name.clone(), column.index))
+            .collect::<Vec<_>>();
+        self.title == other.title
+            && self.intro == other.intro
+            && self.remark == other.remark
+            && self.template == other.template
+            && columns(self) == columns(other)
+            && self.rows == other.rows
+    }
+}
+
+/// 解析 `2024-01-02 09:00` 或 `09:00`（今天已过则为明天），必须晚于当前时间
+pub fn parse_time(input: &str) -> Result<String, String> {
+    parse_time_at(input, Local::now().naive_local())
+}
+
+fn parse_time_at(input: &str, now: NaiveDateTime) -> Result<String, String> {
+    let input = input.trim();
+    let at = match NaiveDateTime::parse_from_str(input, TIME_FORMAT) {
+        Ok(at) => at,
+        Err(_) => {
+            let time = NaiveTime::parse_from_str(input, "%H:%M")
+                .map_err(|_| format!("发送时间格式不正确: {}，请填写 2024-01-02 09:00 或 09:00", input))?;
+            let today = now.date().and_time(time);
+            if today > now { today } else { today + Duration::days(1) }
+        }
+    };
+    if at <= now {
+        return Err(format!("发送时间 {} 已经过去", at.format(TIME_FORMAT)));
+    }
+    Ok(at.format(TIME_FORMAT).to_string())
+}
+
+/// 文件不存在时返回 `None`；读取失败或格式有误时返回错误，修复前不能安排新的定时发送，以免覆盖原来的任务
+pub fn load() -> Result<Option<Scheduled>, String> {
+    match fs::read_to_string(SCHEDULE_FILE) {
+        Ok(data) => serde_json::from_str(&data).map(Some).map_err(|e| format!("定时发送 {} 已损坏: {}", SCHEDULE_FILE, e)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("读取定时发送 {} 失败: {}", SCHEDULE_FILE, e)),
+    }
+}
+
+pub fn save(scheduled: &Scheduled) -> Result<(), String> {
+    let data = serde_json::to_string(scheduled).map_err(|e| e.to_string())?;
+    fs::write(SCHEDULE_FILE, data).map_err(|e| format!("保存定时发送失败: {}", e))
+}
+
+pub fn clear() {
+    fs::remove_file(SCHEDULE_FILE).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, TIME_FORMAT).unwrap()
+    }
+
+    #[test]
+    fn time_of_day_is_today_or_tomorrow() {
+        let now = at("2024-01-02 10:30");
+        assert_eq!(parse_time_at("11:00", now).unwrap(), "2024-01-02 11:00");
+        assert_eq!(parse_time_at(" 09:00 ", now).unwrap(), "2024-01-03 09:00");
+        // 正好是现在的时间也算已过
+        assert_eq!(parse_time_at("10:30", now).unwrap(), "2024-01-03 10:30");
+        assert_eq!(parse_time_at("23:59", at("2024-12-31 23:59")).unwrap(), "2025-01-01 23:59");
+    }
+
+    #[test]
+    fn past_or_invalid_times_are_rejected() {
+        let now = at("2024-01-02 10:30");
+        assert_eq!(parse_time_at("2024-01-02 11:00", now).unwrap(), "2024-01-02 11:00");
+        assert!(parse_time_at("2024-01-02 10:30", now).unwrap_err().contains("已经过去"));
+        assert!(parse_time_at("2023-12-31 09:00", now).unwrap_err().contains("已经过去"));
+        assert!(parse_time_at("25:00", now).unwrap_err().contains("格式不正确"));
+        assert!(parse_time_at("", now).is_err());
+    }
+}
@@ -0,0 +1,25 @@
+use crate::{Header, Tasks, FIXED_COLUMNS};
+
+/// 判断一行数据是否满足搜索条件
+///
+/// 多个条件用空格分隔，需同时满足；`列名:值` 只在该列中查找，其余条件在邮箱、姓名及所有列中查找，均不区分大小写。
+pub fn matches(task: &Tasks, headers: &[Header], query: &str) -> bool {
+    query.split_whitespace().all(|term| {
+        let term = term.to_lowercase();
+        match term.split_once([':', '：']) {
+            Some((column, value)) if !column.is_empty() => {
+                headers.iter()
+                    .skip(FIXED_COLUMNS)
+                    .find(|header| header.name.to_lowercase() == column)
+                    .is_some_and(|header| task.value(header.index).to_lowercase().contains(value))
+            }
+            _ => {
+                task.email.to_lowercase().contains(&term)
+                    || task.name.to_lowercase().contains(&term)
+                    || headers.iter()
+                    .skip(FIXED_COLUMNS)
+                    .any(|header| task.value(header.index).to_lowercase().contains(&term))
+            }
+        }
+    })
+}
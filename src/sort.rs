@@ -0,0 +1,92 @@
+use std::cmp::Ordering;
+use pinyin::ToPinyin;
+
+/// 一列的排序方式，按整列而不是每两个值决定，保证排序结果前后一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// 非空的值都是数字时按数值排序，空值排在最前
+    Number,
+    /// 否则按拼音排序，汉字以外的字符不区分大小写
+    Text,
+}
+
+impl Kind {
+    pub fn of<S: AsRef<str>>(values: impl IntoIterator<Item = S>) -> Kind {
+        let numeric = values.into_iter().all(|value| {
+            let value = value.as_ref().trim();
+            value.is_empty() || value.parse::<f64>().is_ok()
+        });
+        if numeric { Kind::Number } else { Kind::Text }
+    }
+}
+
+/// 按该列的排序方式比较两个单元格的值
+pub fn compare(kind: Kind, a: &str, b: &str) -> Ordering {
+    match kind {
+        Kind::Number => compare_numbers(number(a), number(b)).then_with(|| a.cmp(b)),
+        Kind::Text => collation_key(a).cmp(&collation_key(b)).then_with(|| a.cmp(b)),
+    }
+}
+
+fn number(value: &str) -> Option<f64> {
+    value.trim().parse().ok()
+}
+
+fn compare_numbers(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a, b) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        (x, y) => x.is_some().cmp(&y.is_some()),
+    }
+}
+
+fn collation_key(value: &str) -> Vec<String> {
+    value.chars()
+        .map(|c| match c.to_pinyin() {
+            Some(pinyin) => pinyin.plain().to_owned(),
+            None => c.to_lowercase().collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(values: &[&str]) -> Vec<String> {
+        let kind = Kind::of(values);
+        let mut values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        values.sort_by(|a, b| compare(kind, a, b));
+        values
+    }
+
+    #[test]
+    fn numbers_sort_by_value() {
+        assert_eq!(Kind::of(["10", " 2", "", "-1.5"]), Kind::Number);
+        assert_eq!(sorted(&["10", " 2", "", "-1.5"]), vec!["", "-1.5", " 2", "10"]);
+        assert_eq!(sorted(&["NaN", "1", "inf", "-inf"]), vec!["-inf", "1", "inf", "NaN"]);
+    }
+
+    #[test]
+    fn mixed_columns_sort_as_text() {
+        let values = ["10", "张三", "9", "abc", "李四", "Abd", "2"];
+        assert_eq!(Kind::of(values), Kind::Text);
+        assert_eq!(sorted(&values), vec!["10", "2", "9", "abc", "Abd", "李四", "张三"]);
+    }
+
+    #[test]
+    fn mixed_columns_are_transitive() {
+        // 逐对决定数值或文本时 "10" < "9"（文本）、"9" < "a10"、"a10" > "10" 会互相矛盾
+        let values = ["9", "10", "a10", "", "NaN", "8.5"];
+        let kind = Kind::of(values);
+        for a in values {
+            for b in values {
+                assert_eq!(compare(kind, a, b), compare(kind, b, a).reverse());
+                for c in values {
+                    if compare(kind, a, b).is_le() && compare(kind, b, c).is_le() {
+                        assert!(compare(kind, a, c).is_le(), "{} {} {}", a, b, c);
+                    }
+                }
+            }
+        }
+    }
+}
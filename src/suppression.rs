@@ -0,0 +1,104 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use chrono::Local;
+use iced::widget::{button, column, container, horizontal_space, row, scrollable, text};
+use iced::{alignment, theme, Element, Length};
+use serde::{Deserialize, Serialize};
+use crate::{Message, State, SUPPRESSION_FILE};
+
+/// 禁止群发的邮箱（离职、退订、外部重要联系人等）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Entry {
+    pub email: String,
+    pub reason: String,
+    pub added_at: String,
+}
+
+fn key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// 文件不存在时返回空名单；读取失败或格式有误时返回错误，不能当作空名单继续发送
+pub fn load() -> Result<Vec<Entry>, String> {
+    match fs::read_to_string(SUPPRESSION_FILE) {
+        Ok(data) => serde_json::from_str(&data).map_err(|e| format!("禁止发送名单 {} 已损坏: {}", SUPPRESSION_FILE, e)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(format!("读取禁止发送名单 {} 失败: {}", SUPPRESSION_FILE, e)),
+    }
+}
+
+pub fn save(entries: &[Entry]) -> Result<(), String> {
+    let data = serde_json::to_string(entries).map_err(|e| e.to_string())?;
+    fs::write(SUPPRESSION_FILE, data).map_err(|e| format!("写入禁止发送名单失败: {}", e))
+}
+
+/// 返回该邮箱被禁止发送的原因，不在名单中时返回 `None`
+pub fn find<'a>(entries: &'a [Entry], email: &str) -> Option<&'a str> {
+    let email = key(email);
+    entries.iter()
+        .find(|entry| key(&entry.email) == email)
+        .map(|entry| entry.reason.as_str())
+}
+
+/// 加入名单，已存在的邮箱只更新原因；返回是否新加入
+pub fn add(entries: &mut Vec<Entry>, email: &str, reason: &str) -> bool {
+    let reason = if reason.trim().is_empty() { "手动加入" } else { reason.trim() };
+    if let Some(entry) = entries.iter_mut().find(|entry| key(&entry.email) == key(email)) {
+        entry.reason = reason.to_owned();
+        return false;
+    }
+    entries.push(Entry {
+        email: email.trim().to_owned(),
+        reason: reason.to_owned(),
+        added_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    });
+    true
+}
+
+/// 从文本或 csv 文件导入，每行一个邮箱，逗号后面可以写原因；返回新加入的个数
+pub fn import(entries: &mut Vec<Entry>, path: &Path) -> Result<usize, String> {
+    let data = fs::read_to_string(path).map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+    let mut added = 0;
+    for line in data.trim_start_matches('\u{feff}').lines() {
+        let (email, reason) = line.split_once([',', '，', '\t']).unwrap_or((line, ""));
+        let email = email.trim().trim_matches('"');
+        if !crate::mail::is_valid_email(email) {
+            continue;
+        }
+        if add(entries, email, reason.trim().trim_matches('"')) {
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+pub fn view(state: &State) -> Element<'_, Message> {
+    let import = button("从文件导入").padding([5, 10])
+        .on_press(Message::ImportSuppression)
+        .style(theme::Button::Secondary);
+    let close = button("收起").padding([5, 10])
+        .on_press(Message::ToggleSuppression)
+        .style(theme::Button::Secondary);
+    let tools = row![text(format!("禁止发送名单（{} 个）", state.suppression.len())), horizontal_space(), import, close]
+        .spacing(10)
+        .align_items(alignment::Alignment::Center);
+
+    let mut entries = column![].spacing(4);
+    for (index, entry) in state.suppression.iter().enumerate() {
+        entries = entries.push(row![
+            text(&entry.email).width(250),
+            text(&entry.reason).width(Length::Fill),
+            text(&entry.added_at).width(160),
+            button(text("移除").size(14)).padding([2, 6]).on_press(Message::RemoveSuppression(index)).style(theme::Button::Text),
+        ].spacing(10).align_items(alignment::Alignment::Center));
+    }
+
+    container(column![tools, scrollable(entries).width(Length::Fill).height(Length::Fill)].spacing(10))
+        .width(Length::Fill)
+        .height(220)
+        .padding(10)
+        .style(theme::Container::Box)
+        .into()
+}
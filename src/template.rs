@@ -0,0 +1,181 @@
+use crate::{Header, Tasks, FIXED_COLUMNS};
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+/// 按出现顺序列出模板中的 `{{列名}}` 占位符
+pub fn placeholders(template: &str) -> Vec<String> {
+    let mut names = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find(OPEN) {
+        let after = &rest[start + OPEN.len()..];
+        match after.find(CLOSE) {
+            Some(end) => {
+                let name = after[..end].trim().to_owned();
+                if !name.is_empty() && !names.contains(&name) {
+                    names.push(name);
+                }
+                rest = &after[end + CLOSE.len()..];
+            }
+            None => break,
+        }
+    }
+    names
+}
+
+/// 返回模板引用了、但导入的表头中不存在的列名
+pub fn missing_columns(template: &str, headers: &[Header]) -> Vec<String> {
+    placeholders(template)
+        .into_iter()
+        .filter(|name| !has_column(headers, name))
+        .collect()
+}
+
+/// 用当前行的数据替换模板中的占位符，找不到的列原样保留
+pub fn render(template: &str, task: &Tasks, headers: &[Header]) -> String {
+    render_with(template, task, headers, |_, value| value)
+}
+
+/// 同 [`render`]，替换前先用 `f` 处理每个单元格的值
+pub fn render_with(template: &str, task: &Tasks, headers: &[Header], f: impl Fn(&Header, String) -> String) -> String {
+    struct Columns<'a, F> {
+        task: &'a Tasks,
+        headers: &'a [Header],
+        f: F,
+    }
+
+    impl<F: Fn(&Header, String) -> String> Context for Columns<'_, F> {
+        fn value(&self, name: &str) -> Option<String> {
+            column(self.headers, name).map(|header| (self.f)(header, self.task.value(header.index)))
+        }
+    }
+
+    expand(template, &Columns { task, headers, f })
+}
+
+/// 提供模板中占位符和区块的内容
+pub trait Context {
+    /// `{{name}}` 的值，返回 `None` 时占位符原样保留
+    fn value(&self, name: &str) -> Option<String>;
+
+    /// `{{#name}}...{{/name}}` 区块展开后的内容，返回 `None` 时按普通文本处理
+    fn block(&self, _name: &str, _inner: &str) -> Option<String> {
+        None
+    }
+
+    /// 模板中原样输出的文字
+    fn text(&self, literal: &str) -> String {
+        literal.to_owned()
+    }
+}
+
+/// 从左到右展开模板，替换进去的值不会再被当作模板解析
+pub fn expand(template: &str, context: &impl Context) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(OPEN) {
+        result.push_str(&context.text(&rest[..start]));
+        let after = &rest[start + OPEN.len()..];
+        let Some(end) = after.find(CLOSE) else {
+            rest = &rest[start..];
+            break;
+        };
+        let placeholder = &rest[start..start + OPEN.len() + end + CLOSE.len()];
+        let name = after[..end].trim();
+        rest = &after[end + CLOSE.len()..];
+        if let Some(block) = name.strip_prefix('#') {
+            let close = format!("{}/{}{}", OPEN, block, CLOSE);
+            if let Some(end) = rest.find(&close) {
+                if let Some(expanded) = context.block(block, &rest[..end]) {
+                    result.push_str(&expanded);
+                    rest = &rest[end + close.len()..];
+                    continue;
+                }
+            }
+        }
+        match context.value(name) {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&context.text(placeholder)),
+        }
+    }
+    result.push_str(&context.text(rest));
+    result
+}
+
+pub fn has_column(headers: &[Header], name: &str) -> bool {
+    column(headers, name).is_some()
+}
+
+pub fn column<'a>(headers: &'a [Header], name: &str) -> Option<&'a Header> {
+    headers.iter()
+        .skip(FIXED_COLUMNS)
+        .find(|header| header.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::excel::build_headers;
+
+    fn headers() -> Vec<Header> {
+        build_headers(["邮箱地址", "序号", "姓名", "部门"].map(String::from).to_vec())
+    }
+
+    fn task() -> Tasks {
+        Tasks {
+            email: "zhang@example.com".to_owned(),
+            seq: 7,
+            name: "张三".to_owned(),
+            info: vec!["研发".to_owned()],
+            ..Tasks::default()
+        }
+    }
+
+    #[test]
+    fn placeholders_in_order_without_duplicates() {
+        assert_eq!(placeholders("{{ 姓名 }}，{{部门}}的{{姓名}}"), vec!["姓名", "部门"]);
+        assert_eq!(placeholders("{{}} {{未闭合"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn missing_and_existing_columns() {
+        let headers = headers();
+        assert!(has_column(&headers, "部门"));
+        // 固定的"全选"和"发送状态"列不能被引用
+        assert!(!has_column(&headers, "全选"));
+        assert_eq!(missing_columns("{{姓名}}{{工号}}", &headers), vec!["工号"]);
+    }
+
+    #[test]
+    fn render_keeps_unknown_placeholders() {
+        let headers = headers();
+        assert_eq!(render("[{{部门}}]{{姓名}} {{工号}}", &task(), &headers), "[研发]张三 {{工号}}");
+        assert_eq!(render("{{序号}}号 {{未闭合", &task(), &headers), "7号 {{未闭合");
+    }
+
+    #[test]
+    fn render_with_maps_each_value() {
+        let headers = headers();
+        let rendered = render_with("{{姓名}}/{{部门}}", &task(), &headers, |header, value| format!("{}={}", header.name, value));
+        assert_eq!(rendered, "姓名=张三/部门=研发");
+    }
+
+    struct Blocks;
+
+    impl Context for Blocks {
+        fn value(&self, name: &str) -> Option<String> {
+            (name == "x").then(|| "{{y}}".to_owned())
+        }
+
+        fn block(&self, name: &str, inner: &str) -> Option<String> {
+            (name == "twice").then(|| expand(inner, self).repeat(2))
+        }
+    }
+
+    #[test]
+    fn expand_blocks_without_rescanning_values() {
+        assert_eq!(expand("a{{#twice}}[{{x}}]{{/twice}}b", &Blocks), "a[{{y}}][{{y}}]b");
+        assert_eq!(expand("{{#twice}}未闭合", &Blocks), "{{#twice}}未闭合");
+        assert_eq!(expand("{{#other}}1{{/other}}", &Blocks), "{{#other}}1{{/other}}");
+    }
+}
@@ -0,0 +1,74 @@
+use iced::widget::{column, container, row, scrollable, Row, Space};
+use iced::{Element, Length, Theme};
+use iced_table::table::Column;
+use crate::{Message, State, Tasks};
+
+pub const MIN_COLUMN_WIDTH: f32 = 30.0;
+/// 表头高 24，加上表格单元格上下各 4 的内边距
+pub const HEADER_HEIGHT: f32 = 32.0;
+// 单元格高 32，加上上下各 4 的内边距
+const ROW_HEIGHT: f32 = 40.0;
+const CELL_PADDING: u16 = 4;
+const DIVIDER_WIDTH: f32 = 2.0;
+
+/// 可见的行从第几行开始、到第几行结束（不含），`height` 为内容区域的高度
+fn window(offset: f32, height: f32, total: usize) -> (usize, usize) {
+    let visible = (height.max(0.0) / ROW_HEIGHT).ceil() as usize + 1;
+    let start = ((offset.max(0.0) / ROW_HEIGHT) as usize).min(total.saturating_sub(visible));
+    (start, (start + visible).min(total))
+}
+
+/// "全部显示"时的表格内容：只为可见的行生成控件，上下用等高的空白占位，滚动条仍然对应全部的行
+pub fn view(state: &State, height: f32) -> Element<'_, Message> {
+    let total = state.filtered.len();
+    let (start, end) = window(state.row_offset, height, total);
+    let mut rows = column![Space::with_height(start as f32 * ROW_HEIGHT)];
+    for (row_index, index) in state.filtered[start..end].iter().enumerate() {
+        rows = rows.push(table_row(state, start + row_index, &state.list[*index]));
+    }
+    rows = rows.push(Space::with_height((total - end) as f32 * ROW_HEIGHT));
+
+    scrollable(rows)
+        .id(state.rows.clone())
+        .on_scroll(|viewport| Message::ScrollRows(viewport.absolute_offset()))
+        .direction(scrollable::Direction::Both {
+            vertical: scrollable::Properties::default(),
+            horizontal: scrollable::Properties::default(),
+        })
+        .height(Length::Fill)
+        .into()
+}
+
+// 与 iced_table 的行保持相同的列宽、内边距和隔行底色，和表头对齐
+fn table_row<'a>(state: &'a State, row_index: usize, task: &'a Tasks) -> Element<'a, Message> {
+    let cells = state.headers.iter().enumerate().map(|(col_index, header)| {
+        let width = (header.width() + header.resize_offset().unwrap_or_default()).max(MIN_COLUMN_WIDTH);
+        row![
+            container(header.cell(col_index, row_index, task)).width(Length::Fill).padding(CELL_PADDING),
+            Space::new(DIVIDER_WIDTH, Length::Shrink),
+        ].width(width).into()
+    });
+    container(Row::with_children(cells))
+        .style(move |theme: &Theme| iced_table::StyleSheet::row(theme, &(), row_index))
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_covers_the_visible_rows() {
+        // 高 100 能显示 2.5 行，多生成一行以便滚动到一半时也不露空
+        assert_eq!(window(0.0, 100.0, 1000), (0, 4));
+        assert_eq!(window(410.0, 100.0, 1000), (10, 14));
+    }
+
+    #[test]
+    fn window_is_clamped_to_the_list() {
+        assert_eq!(window(0.0, 100.0, 2), (0, 2));
+        // 列表被筛选变短后，旧的滚动位置落在最后几行
+        assert_eq!(window(40_000.0, 100.0, 10), (6, 10));
+        assert_eq!(window(-5.0, -1.0, 0), (0, 0));
+    }
+}